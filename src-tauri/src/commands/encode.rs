@@ -1,19 +1,24 @@
 use std::path::Path;
 use tauri::State;
 
-use crate::ffmpeg::{args, runner};
+use crate::ffmpeg::{args, capability, command, compat, runner, trim};
 use crate::state::AppState;
-use crate::types::{EncodeParams, VideoInfo};
+use crate::types::{
+    CommandPreview, CompatReport, EncodeParams, EncodeStarted, EncoderCapabilities, EncoderChoice,
+    EncoderGroup, MediaInfo, ScriptShell, VideoInfo,
+};
 
+use std::io::Write;
 use std::process::Command;
 
 /// 开始编码任务
 #[tauri::command]
 pub async fn start_encode(
     params: EncodeParams,
+    index: Option<usize>,
     state: State<'_, AppState>,
     app_handle: tauri::AppHandle,
-) -> Result<String, String> {
+) -> Result<EncodeStarted, String> {
     // 检查是否已有任务在运行
     if state.is_running()? {
         return Err("已有编码任务在运行".to_string());
@@ -29,29 +34,42 @@ pub async fn start_encode(
     if !Path::new(&params.output_dir).is_dir() {
         return Err(format!("输出目录无效: {}", params.output_dir));
     }
-
-    // 探测视频时长
-    match runner::probe_duration(&params.video_path) {
-        Ok(duration) => {
-            state.set_total_duration(duration)?;
+    if let EncoderChoice::Specific { encoder } = &params.encoder_choice {
+        let listed = capability::ffmpeg_encoders()?;
+        if !listed.iter().any(|e| e.video && &e.name == encoder) {
+            return Err(format!("当前 FFmpeg 不支持视频编码器: {encoder}"));
         }
+    }
+
+    // 探测视频时长，剪辑时进度按剪辑后的时长计算
+    let duration = match runner::probe_duration(&params.video_path) {
+        Ok(duration) => duration,
         Err(e) => {
             log::warn!("无法探测视频时长: {e}，进度百分比将不可用");
-            state.set_total_duration(0.0)?;
+            0.0
         }
+    };
+    trim::validate_ranges(&params.trim, duration)?;
+    state.set_total_duration(trim::output_duration(&params.trim, duration))?;
+
+    // 构建输出路径，模板中的子目录在这里创建
+    let output_path = runner::resolve_output_path(&params, index.unwrap_or(1))?;
+    if let Some(parent) = Path::new(&output_path).parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("创建输出目录失败: {e}"))?;
     }
 
     // 设置运行状态
     state.set_running(true)?;
 
-    // 构建输出路径
-    let output_path = args::build_output_path(&params);
-
     // 启动编码
     match runner::spawn_encode(&params, &output_path, &state, &app_handle) {
-        Ok(()) => {
-            log::info!("FFmpeg 进程已启动，输出: {output_path}");
-            Ok(format!("编码已开始，输出文件: {output_path}"))
+        Ok(encoder) => {
+            log::info!("FFmpeg 进程已启动，编码器: {encoder}，输出: {output_path}");
+            Ok(EncodeStarted {
+                notes: args::two_pass_note(&params, &encoder).into_iter().collect(),
+                output_path,
+                encoder,
+            })
         }
         Err(e) => {
             state.set_running(false)?;
@@ -60,6 +78,104 @@ pub async fn start_encode(
     }
 }
 
+/// 检查编码参数与目标容器的兼容性，返回问题列表和修改建议（不启动编码）
+#[tauri::command]
+pub async fn check_compatibility(params: EncodeParams) -> Result<CompatReport, String> {
+    if !Path::new(&params.video_path).exists() {
+        return Err(format!("视频文件不存在: {}", params.video_path));
+    }
+    let encoder = args::select_encoder(&params);
+    let info = runner::probe_media_info(&params.video_path)?;
+    let source = runner::source_codecs(&params, &info)?;
+    Ok(compat::check(&params, &encoder, &source))
+}
+
+/// 生成编码命令而不执行，用于复制到终端或在其他机器上运行
+#[tauri::command]
+pub async fn preview_command(
+    params: EncodeParams,
+    index: Option<usize>,
+) -> Result<CommandPreview, String> {
+    if !Path::new(&params.video_path).exists() {
+        return Err(format!("视频文件不存在: {}", params.video_path));
+    }
+    let output_path = runner::resolve_output_path(&params, index.unwrap_or(1))?;
+    let plan = runner::plan_commands(&params, &output_path, "ffsub-pass")?;
+    Ok(CommandPreview {
+        encoder: plan.encoder,
+        output_path,
+        commands: plan.passes.iter().map(|pass| command::command_line(pass)).collect(),
+        notes: plan.notes,
+    })
+}
+
+/// 把一组编码任务导出为批处理脚本，任一任务失败时脚本中止
+///
+/// 文件扩展名必须与脚本格式一致；目标文件已存在时只有 `overwrite` 为真才覆盖，且保留其原有权限。
+#[tauri::command]
+pub async fn export_script(
+    queue: Vec<EncodeParams>,
+    shell: ScriptShell,
+    path: String,
+    overwrite: Option<bool>,
+) -> Result<(), String> {
+    if queue.is_empty() {
+        return Err("没有可导出的任务".to_string());
+    }
+    let extension = command::extension(shell);
+    if !Path::new(&path)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case(extension))
+    {
+        return Err(format!("脚本文件的扩展名必须为 .{extension}: {path}"));
+    }
+    let exists = Path::new(&path).exists();
+    if exists && !overwrite.unwrap_or(false) {
+        return Err(format!("文件已存在: {path}"));
+    }
+    let mut jobs = Vec::with_capacity(queue.len());
+    for (i, params) in queue.iter().enumerate() {
+        if !Path::new(&params.video_path).exists() {
+            return Err(format!("视频文件不存在: {}", params.video_path));
+        }
+        let output_path = runner::resolve_output_path(params, i + 1)?;
+        // 每个任务使用独立的统计日志前缀
+        let log_prefix = format!("ffsub-pass-{}", i + 1);
+        let plan = runner::plan_commands(params, &output_path, &log_prefix)
+            .map_err(|e| format!("{}: {e}", params.video_path))?;
+        jobs.push(command::ScriptJob {
+            title: format!("{} -> {output_path}（{}）", params.video_path, plan.encoder),
+            output_dir: Path::new(&output_path)
+                .parent()
+                .map(|p| p.to_string_lossy().into_owned()),
+            passes: plan.passes,
+            notes: plan.notes,
+        });
+    }
+
+    // 新建文件时使用 create_new，避免检查之后同名文件被创建而被意外覆盖
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true);
+    if exists {
+        options.truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    options
+        .open(&path)
+        .and_then(|mut file| file.write_all(command::script(shell, &jobs).as_bytes()))
+        .map_err(|e| format!("写入脚本失败: {e}"))?;
+    // 只给本次新建的脚本添加执行权限
+    #[cfg(unix)]
+    if shell == ScriptShell::Posix && !exists {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+            .map_err(|e| format!("设置脚本权限失败: {e}"))?;
+    }
+    log::info!("已导出 {} 个任务的脚本: {path}", jobs.len());
+    Ok(())
+}
+
 /// 停止当前编码任务
 #[tauri::command]
 pub async fn stop_encode(state: State<'_, AppState>) -> Result<(), String> {
@@ -90,6 +206,53 @@ pub async fn get_video_info(path: String) -> Result<VideoInfo, String> {
     runner::probe_video_info(&path)
 }
 
+/// 获取媒体文件的完整信息：所有视频/音频/字幕流、附件、章节和容器元数据
+#[tauri::command]
+pub async fn get_media_info(path: String) -> Result<MediaInfo, String> {
+    runner::probe_media_info(&path)
+}
+
+/// 获取硬件编码器探测结果，`refresh` 为真时忽略缓存重新进行测试编码；
+/// `vaapi_device` 为测试 VAAPI 编码器使用的渲染节点，缺省为默认节点
+#[tauri::command]
+pub async fn probe_hw_encoders(
+    vaapi_device: Option<String>,
+    refresh: Option<bool>,
+) -> Result<EncoderCapabilities, String> {
+    // 测试编码可能耗时数十秒，不占用异步运行时的工作线程
+    tauri::async_runtime::spawn_blocking(move || {
+        capability::hw_capabilities(vaapi_device.as_deref(), refresh.unwrap_or(false))
+    })
+    .await
+    .map_err(|e| format!("硬件编码器探测任务失败: {e}"))?
+}
+
+/// 按编码格式分组列出 FFmpeg 中的所有视频编码器
+#[tauri::command]
+pub async fn list_encoders(vaapi_device: Option<String>) -> Result<Vec<EncoderGroup>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        capability::list_video_encoders(vaapi_device.as_deref())
+    })
+    .await
+    .map_err(|e| format!("列出编码器任务失败: {e}"))?
+}
+
+/// 列出可供 VAAPI 使用的 DRI 渲染节点（仅 Linux，其他平台返回空列表）
+#[tauri::command]
+pub async fn list_vaapi_devices() -> Result<Vec<String>, String> {
+    let Ok(entries) = std::fs::read_dir("/dev/dri") else {
+        return Ok(Vec::new());
+    };
+
+    let mut devices: Vec<String> = entries
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with("renderD"))
+        .map(|e| e.path().to_string_lossy().to_string())
+        .collect();
+    devices.sort();
+    Ok(devices)
+}
+
 /// 获取默认输出目录（优先系统视频目录），并在其下创建 `FFSub` 子文件夹
 #[tauri::command]
 pub async fn get_default_output_dir() -> Result<String, String> {
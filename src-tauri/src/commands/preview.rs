@@ -0,0 +1,94 @@
+use std::path::{Path, PathBuf};
+use tauri::{Manager, State};
+
+use crate::ffmpeg::args::ClipRange;
+use crate::ffmpeg::{runner, subtitle, trim};
+use crate::state::AppState;
+use crate::types::{EncodeParams, PreviewClip};
+
+/// 预览片段默认时长（秒）
+const DEFAULT_PREVIEW_SECS: f64 = 10.0;
+
+/// 使用与正式任务相同的参数渲染一小段预览，用于检查卡拉 OK、淡入淡出和移动等动态效果
+///
+/// `timestamp`（秒）与 `cue_index`（从 0 开始的字幕条目序号）二选一，均未提供时从视频开头截取 `duration` 秒。
+/// 预览与编码任务共用运行状态：编码期间不能预览，渲染中的预览可由 `stop_encode` 停止。
+#[tauri::command]
+pub async fn render_preview(
+    mut params: EncodeParams,
+    timestamp: Option<f64>,
+    cue_index: Option<usize>,
+    duration: Option<f64>,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<PreviewClip, String> {
+    if state.is_running()? {
+        return Err("已有编码任务在运行".to_string());
+    }
+    if !Path::new(&params.video_path).exists() {
+        return Err(format!("视频文件不存在: {}", params.video_path));
+    }
+    if !Path::new(&params.subtitle_path).exists() {
+        return Err(format!("字幕文件不存在: {}", params.subtitle_path));
+    }
+
+    let length = duration.unwrap_or(DEFAULT_PREVIEW_SECS);
+    if length <= 0.0 {
+        return Err("预览时长必须大于 0".to_string());
+    }
+
+    let total = runner::probe_duration(&params.video_path).unwrap_or(0.0);
+    // 目标大小模式按剪辑后的整片时长换算码率，预览片段使用与正式编码相同的码率
+    let encoded = trim::output_duration(&params.trim, total);
+    runner::resolve_size_target(&mut params, encoded)?;
+
+    let clip = match (cue_index, timestamp) {
+        (Some(index), _) => {
+            let cues = subtitle::read_cues(&params.subtitle_path)?;
+            let cue = cues
+                .get(index)
+                .copied()
+                .ok_or_else(|| format!("字幕条目序号超出范围: {index}（共 {} 条）", cues.len()))?;
+            ClipRange::around_cue(cue, length, total)
+        }
+        (None, Some(t)) => ClipRange::around(t, length, total),
+        (None, None) => ClipRange::starting_at(0.0, length, total),
+    };
+
+    let output_path = preview_output_path(&params, clip)?.to_string_lossy().to_string();
+
+    // FFmpeg 在阻塞线程中运行，不占用异步运行时
+    state.set_running(true)?;
+    let path = output_path.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        runner::render_preview(&params, clip, &path, &app_handle.state::<AppState>())
+    })
+    .await
+    .map_err(|e| format!("预览任务失败: {e}"));
+    state.set_running(false)?;
+    result??;
+
+    Ok(PreviewClip {
+        path: output_path,
+        start: clip.start,
+        duration: clip.duration,
+    })
+}
+
+/// 预览文件写入系统临时目录下的 `ffsub-preview` 文件夹
+fn preview_output_path(params: &EncodeParams, clip: ClipRange) -> Result<PathBuf, String> {
+    let mut dir = std::env::temp_dir();
+    dir.push("ffsub-preview");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("创建临时目录失败: {e}"))?;
+
+    let stem = Path::new(&params.video_path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("preview");
+    dir.push(format!(
+        "{stem}_preview_{}ms.{}",
+        (clip.start * 1000.0).round() as u64,
+        params.output_format
+    ));
+    Ok(dir)
+}
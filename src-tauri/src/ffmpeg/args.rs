@@ -1,10 +1,17 @@
-use crate::types::EncodeParams;
+use crate::ffmpeg::audio;
+use crate::ffmpeg::capability;
+use crate::ffmpeg::compat;
+use crate::ffmpeg::encoder::{self, BitrateMode, EncoderFamily};
+use crate::ffmpeg::extra;
+use crate::ffmpeg::filter::{Filter, FilterChain};
+use crate::ffmpeg::loudnorm;
+use crate::ffmpeg::subtitle::Cue;
+use crate::ffmpeg::template;
+use crate::ffmpeg::trim;
+use crate::ffmpeg::video;
+use crate::ffmpeg::x26x;
+use crate::types::{EncodeParams, EncoderChoice, RateControl, SpeedPreset};
 use std::path::Path;
-use std::process::Command;
-use std::sync::OnceLock;
-
-/// 缓存 GPU 编码器检测结果，避免每次编码都启动 FFmpeg 进程
-static GPU_ENCODER_CACHE: OnceLock<Option<String>> = OnceLock::new();
 
 /// 获取 FFmpeg 可执行文件路径
 pub fn ffmpeg_bin() -> &'static str {
@@ -24,121 +31,332 @@ pub fn ffprobe_bin() -> &'static str {
     }
 }
 
-/// 对字幕路径进行安全转义，防止 FFmpeg filter 注入
-fn escape_filter_path(path: &str) -> String {
-    // For FFmpeg filtergraphs we must escape characters that are used as
-    // separators in filter option parsing. Keep backslashes and escape them
-    // instead of converting to forward slashes — this preserves Windows
-    // drive letters like `C:` while escaping the colon and other separators.
-    path.replace('\\', "\\\\")
-        .replace(':', "\\:")
-        .replace(',', "\\,")
-        .replace("'", "\\'")
-        .replace('[', "\\[")
-        .replace(']', "\\]")
-        .replace(';', "\\;")
+/// 预览片段的时间范围（秒）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClipRange {
+    pub start: f64,
+    pub duration: f64,
 }
 
-/// 构建字幕滤镜字符串
-fn build_subtitle_filter(params: &EncodeParams) -> String {
-    let escaped = escape_filter_path(&params.subtitle_path);
+impl ClipRange {
+    /// 以 `center` 为中心截取 `duration` 秒，并限制在视频时长范围内（总时长未知时传 0）
+    pub fn around(center: f64, duration: f64, total: f64) -> Self {
+        Self::starting_at(center - duration / 2.0, duration, total)
+    }
 
-    let mut filter = format!("subtitles='{escaped}'");
+    /// 从 `start` 起截取 `duration` 秒，超出视频结尾时向前移动（总时长未知时传 0）
+    pub fn starting_at(start: f64, duration: f64, total: f64) -> Self {
+        let mut start = start.max(0.0);
+        if total > 0.0 && start + duration > total {
+            start = (total - duration).max(0.0);
+        }
+        let duration = if total > 0.0 { duration.min(total - start) } else { duration };
+        Self { start, duration }
+    }
 
-    if params.subtitle_encoding != "utf8" {
-        filter.push_str(&format!(":charenc={}", params.subtitle_encoding));
+    /// 截取覆盖指定字幕条目的片段：短于片段长度的条目居中，较长的条目从其开始前 1 秒起截取
+    pub fn around_cue(cue: Cue, duration: f64, total: f64) -> Self {
+        let length = cue.end - cue.start;
+        if length < duration {
+            Self::around(cue.start + length / 2.0, duration, total)
+        } else {
+            Self::around((cue.start - 1.0).max(0.0) + duration / 2.0, duration, total)
+        }
+    }
+}
+
+/// 构建字幕滤镜链
+///
+/// 截取片段时输入端快速 seek 会把时间戳归零，先用 `setpts` 恢复原始时间戳，
+/// 字幕才能按原时间轴渲染，之后再归零以匹配输出。`seek` 为截取起点（秒）。
+/// 缩放和帧率转换在字幕之前执行，字幕按输出分辨率渲染。
+fn build_subtitle_filter(params: &EncodeParams, seek: Option<f64>) -> FilterChain {
+    let mut chain = FilterChain::new();
+
+    if let Some(start) = seek {
+        chain.push(Filter::new("setpts").arg(format!("PTS+{start:.3}/TB")));
+    }
+    for filter in video::pre_subtitle_filters(params) {
+        chain.push(filter);
     }
 
+    let mut subtitles = Filter::new("subtitles").opt("filename", params.subtitle_path.as_str());
+    if params.subtitle_encoding != "utf8" {
+        subtitles = subtitles.opt("charenc", params.subtitle_encoding.as_str());
+    }
     if params.subtitle_style == "custom" {
-        filter.push_str(":force_style='PrimaryColour=&H00FFFFFF'");
+        subtitles = subtitles.opt("force_style", "PrimaryColour=&H00FFFFFF");
+    }
+    // ASS 坐标按播放时的画面尺寸编写，旋转或拉伸像素后需告知 libass 原始尺寸
+    if let Some(src) = &params.source_video {
+        let (width, height) = video::display_size(src);
+        subtitles = subtitles.opt("original_size", format!("{width}x{height}"));
+    }
+    chain.push(subtitles);
+
+    if seek.is_some() {
+        chain.push(Filter::new("setpts").arg("PTS-STARTPTS"));
     }
 
-    filter
+    chain
 }
 
-/// 生成输出文件路径
-pub fn build_output_path(params: &EncodeParams) -> String {
-    let video_name = Path::new(&params.video_path)
+/// 按文件名模板生成输出文件路径，`index` 为任务序号（从 1 开始）
+///
+/// `{height}` 需要已探测的源视频信息（`source_video`）。
+pub fn build_output_path(params: &EncodeParams, index: usize) -> Result<String, String> {
+    let stem = Path::new(&params.video_path)
         .file_stem()
         .and_then(|s| s.to_str())
         .unwrap_or("output");
+    let vars = template::OutputVars {
+        stem: stem.to_string(),
+        ext: params.output_format.clone(),
+        codec: template::codec_name(&output_codec(params)),
+        crf: params.crf,
+        height: video::output_height(params),
+        lang: template::subtitle_language(&params.subtitle_path),
+        date: template::today(),
+        index,
+    };
+    let name = params
+        .output_template
+        .as_deref()
+        .filter(|t| !t.trim().is_empty())
+        .unwrap_or(template::DEFAULT_TEMPLATE);
+    let path = Path::new(&params.output_dir).join(template::render(name, &vars)?);
+
+    if path == Path::new(&params.video_path) || path == Path::new(&params.subtitle_path) {
+        return Err(format!("输出文件不能覆盖输入文件: {}", path.display()));
+    }
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// 命名用的编码器，不探测硬件编码器（硬件与软件编码器的编码格式相同）
+fn output_codec(params: &EncodeParams) -> String {
+    match &params.encoder_choice {
+        EncoderChoice::Specific { encoder } => encoder.clone(),
+        _ if params.video_codec == "copy" => "libx264".to_string(),
+        _ => params.video_codec.clone(),
+    }
+}
 
-    format!(
-        "{}/{}_sub.{}",
-        params.output_dir, video_name, params.output_format
+/// 未指定渲染节点时使用的 VAAPI 设备
+pub const DEFAULT_VAAPI_DEVICE: &str = "/dev/dri/renderD128";
+
+/// 两遍编码中的某一遍
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PassSpec<'a> {
+    /// 第几遍（1 或 2）
+    pub pass: u8,
+    /// 统计日志文件名前缀，相对于 FFmpeg 的工作目录
+    pub log_prefix: &'a str,
+}
+
+/// 为编码任务构建完整的 FFmpeg 参数列表，`encoder` 为 [`select_encoder`] 选出的实际编码器
+pub fn build_encode_args(
+    params: &EncodeParams,
+    encoder: &str,
+    output_path: &str,
+) -> Result<Vec<String>, String> {
+    build_args(params, encoder, output_path, None, None)
+}
+
+/// 构建两遍编码中某一遍的参数，第一遍丢弃音频并输出到空设备
+pub fn build_pass_args(
+    params: &EncodeParams,
+    encoder: &str,
+    output_path: &str,
+    pass: PassSpec,
+) -> Result<Vec<String>, String> {
+    build_args(params, encoder, output_path, None, Some(pass))
+}
+
+/// 构建预览片段的 FFmpeg 参数：与正式任务相同的编码参数，但只截取一小段并使用快速预设
+pub fn build_preview_args(
+    params: &EncodeParams,
+    encoder: &str,
+    clip: ClipRange,
+    output_path: &str,
+) -> Result<Vec<String>, String> {
+    build_args(params, encoder, output_path, Some(clip), None)
+}
+
+/// 任务是否需要两遍编码（硬件编码器和 SVT-AV1 不支持 `-pass`，退化为单遍平均码率）
+pub fn needs_two_pass(params: &EncodeParams, encoder: &str) -> bool {
+    matches!(params.rate_control, RateControl::TwoPass { .. })
+        && EncoderFamily::of(encoder).supports_two_pass()
+}
+
+/// 码率控制模式是否要求两遍编码（两遍 VBR 或目标大小）
+fn wants_two_pass(params: &EncodeParams) -> bool {
+    matches!(
+        params.rate_control,
+        RateControl::TwoPass { .. } | RateControl::TargetSize { .. }
     )
 }
 
-/// 为编码任务构建完整的 FFmpeg 参数列表
-pub fn build_encode_args(params: &EncodeParams, output_path: &str) -> Vec<String> {
-    let subtitle_filter = build_subtitle_filter(params);
-
-    // 使用缓存的硬件编码器检测结果
-    let hw = GPU_ENCODER_CACHE
-        .get_or_init(detect_gpu_encoder)
-        .as_deref();
-
-    // 根据用户选择的逻辑编码器和检测到的硬件支持，选择实际使用的编码器
-    let selected_codec = match params.video_codec.as_str() {
-        "libx264" => match hw {
-            Some("nvenc") => "h264_nvenc",
-            Some("qsv") => "h264_qsv",
-            Some("vaapi") => "h264_vaapi",
-            Some("amf") => "h264_amf",
-            _ => "libx264",
-        },
-        "libx265" => match hw {
-            Some("nvenc") => "hevc_nvenc",
-            Some("qsv") => "hevc_qsv",
-            Some("vaapi") => "hevc_vaapi",
-            Some("amf") => "hevc_amf",
-            _ => "libx265",
-        },
+/// 所选编码器不支持两遍编码、任务退化为单遍平均码率时的说明
+pub fn two_pass_note(params: &EncodeParams, encoder: &str) -> Option<String> {
+    (wants_two_pass(params) && !EncoderFamily::of(encoder).supports_two_pass()).then(|| {
+        format!("{encoder} 不支持两遍编码，将按单遍平均码率编码，码率和文件大小的偏差可能较大")
+    })
+}
+
+/// 根据编码器选择方式、用户选择的逻辑编码器和经测试编码确认可用的硬件编码器，选择实际使用的编码器
+///
+/// 只读取已缓存的探测结果，硬件编码器尚未探测完成时使用软件编码器。
+pub fn select_encoder(params: &EncodeParams) -> String {
+    if let EncoderChoice::Specific { encoder } = &params.encoder_choice {
+        return encoder.clone();
+    }
+
+    let software = match params.video_codec.as_str() {
+        // 如果用户选择了 copy，但我们使用了 subtitle filter，streamcopy 与滤镜不能共存
+        "copy" => {
+            log::warn!("用户选择了 'copy' 编码，但使用了字幕滤镜，已改为 libx264 以支持滤镜");
+            "libx264"
+        }
         other => other,
     };
+    // 硬件编码器不支持两遍编码，自动选择时优先保证码率控制
+    if params.encoder_choice == EncoderChoice::Software || wants_two_pass(params) {
+        return software.to_string();
+    }
 
-    let mut args = vec![
-        "-i".to_string(),
-        params.video_path.clone(),
-        "-vf".to_string(),
-        subtitle_filter,
-    ];
+    let prefix = match software {
+        "libx264" => "h264_",
+        "libx265" => "hevc_",
+        other => return other.to_string(),
+    };
+    capability::cached_hw_encoders(params.vaapi_device.as_deref())
+        .into_iter()
+        .find(|e| e.starts_with(prefix))
+        .unwrap_or_else(|| software.to_string())
+}
 
-    // 视频编码器
-    args.push("-c:v".to_string());
-    // 如果用户选择了 copy，但我们使用了 subtitle filter，streamcopy 与滤镜不能共存
-    let final_codec = if selected_codec == "copy" {
-        log::warn!("用户选择了 'copy' 编码，但使用了字幕滤镜，已改为 libx264 以支持滤镜");
-        "libx264"
-    } else {
-        selected_codec
+fn build_args(
+    params: &EncodeParams,
+    encoder: &str,
+    output_path: &str,
+    clip: Option<ClipRange>,
+    pass: Option<PassSpec>,
+) -> Result<Vec<String>, String> {
+    let family = EncoderFamily::of(encoder);
+    let first_pass = matches!(pass, Some(PassSpec { pass: 1, .. }));
+
+    // 输入端 seek 速度快，配合字幕滤镜中的 setpts 保持时间轴同步；
+    // 预览片段按原视频时间轴截取，不受剪辑范围影响
+    let seek = match clip {
+        Some(clip) => Some((clip.start, Some(clip.duration))),
+        None => trim::single_range(&params.trim).map(|r| (r.start, r.end.map(|end| end - r.start))),
+    };
+    let trim_ranges = match clip {
+        None if params.trim.len() > 1 => params.trim.as_slice(),
+        _ => &[],
     };
+    let mut video_filter = build_subtitle_filter(params, seek.map(|(start, _)| start));
 
-    args.push(final_codec.to_string());
+    let mut args = Vec::new();
 
-    // 质量参数：软件编码用 CRF，硬件编码用对应的质量参数
-    if params.video_codec != "copy" {
-        let is_hw = matches!(hw, Some("nvenc" | "qsv" | "amf" | "vaapi"));
-        if is_hw && final_codec != "libx264" && final_codec != "libx265" {
-            // 硬件编码器使用全局质量参数，数值映射与 CRF 近似
-            args.push("-global_quality".to_string());
-            args.push(params.crf.to_string());
+    // VAAPI 编码器只接受显存中的帧：字幕仍由软件渲染，之后转为 nv12（10-bit 为 p010）并上传到指定的渲染节点
+    let mut upload = FilterChain::new();
+    if family == EncoderFamily::Vaapi {
+        args.push("-vaapi_device".to_string());
+        args.push(
+            params
+                .vaapi_device
+                .clone()
+                .unwrap_or_else(|| DEFAULT_VAAPI_DEVICE.to_string()),
+        );
+        upload.push(Filter::new("format").arg(video::upload_format(params, encoder)));
+        upload.push(Filter::new("hwupload"));
+    }
+
+    if let Some((start, duration)) = seek {
+        args.extend(trim::input_seek_args(start, duration));
+    }
+    args.extend(extra::input_args(&params.extra_args)?);
+    args.push("-i".to_string());
+    args.push(params.video_path.clone());
+
+    if trim_ranges.is_empty() {
+        video_filter.append(upload);
+        args.push("-vf".to_string());
+        args.push(video_filter.to_string());
+        args.extend(audio::map_args(&params.audio_tracks));
+    } else {
+        // 多段剪辑：字幕按原时间轴烧录后再截取拼接，音频在滤镜图中同步截取
+        let audio_inputs = if first_pass {
+            Vec::new()
         } else {
-            args.push("-crf".to_string());
-            args.push(params.crf.to_string());
-            // 软件编码使用较快的预设以平衡速度和质量
-            args.push("-preset".to_string());
-            args.push("medium".to_string());
+            audio::stream_specifiers(&params.audio_tracks)
+        };
+        let audio_post = loudnorm::track_chains(params);
+        let trim = trim::concat_graph(trim_ranges, video_filter, upload, &audio_inputs, &audio_post);
+        args.push("-filter_complex".to_string());
+        args.push(trim.graph.to_string());
+        for label in std::iter::once(&trim.video).chain(&trim.audio) {
+            args.push("-map".to_string());
+            args.push(format!("[{label}]"));
         }
     }
 
+    // 视频编码器
+    args.push("-c:v".to_string());
+    args.push(encoder.to_string());
+
+    // 质量与速度参数：copy 已被替换为默认参数的 libx264，不额外指定
+    if params.video_codec != "copy" {
+        push_rate_control_args(&mut args, params, encoder, pass)?;
+        // 预览片段优先速度，不使用自定义 preset
+        match x26x::preset(params, encoder).filter(|_| clip.is_none()) {
+            Some(preset) => args.extend(["-preset".to_string(), preset.to_string()]),
+            None => {
+                let speed = if clip.is_some() { SpeedPreset::Fastest } else { params.speed };
+                args.extend(encoder::speed_args(encoder, speed));
+            }
+        }
+        args.extend(x26x::args(params, encoder, pass));
+    }
+
     // 启用多线程（0 = 自动检测 CPU 核心数）
     args.push("-threads".to_string());
     args.push("0".to_string());
 
-    // 音频直接复制
+    args.extend(video::color_args(params, encoder));
+
+    // 两遍编码的第一遍只需要视频统计信息，丢弃音频并输出到空设备
+    if first_pass {
+        args.extend(extra::output_args(&params.extra_args)?);
+        args.extend(["-an", "-f", "null", "-y", "-"].map(String::from));
+        return Ok(args);
+    }
+
+    // 音频默认直接复制，与容器不兼容时由兼容性检查选择转码用的编码器
     args.push("-c:a".to_string());
-    args.push("copy".to_string());
+    match &params.audio_codec {
+        Some(audio_encoder) => {
+            args.push(audio_encoder.clone());
+            if let Some(kbps) = compat::default_audio_bitrate(audio_encoder) {
+                args.push("-b:a".to_string());
+                args.push(format!("{kbps}k"));
+            }
+        }
+        None => args.push("copy".to_string()),
+    }
+    args.extend(audio::encode_args(&params.audio_tracks));
+    // 多段剪辑时标准化滤镜已放在滤镜图中
+    if trim_ranges.is_empty() {
+        args.extend(loudnorm::filter_args(params));
+    }
+
+    args.extend(audio::disposition_args(&params.audio_tracks));
+
+    // 容器无法写入源文件的内嵌字幕（如 MP4 中的位图字幕）时丢弃，由兼容性检查报告
+    if params.drop_subtitles {
+        args.push("-sn".to_string());
+    }
 
     // MP4 格式启用 faststart，将 moov atom 移到文件开头，加速播放启动
     if params.output_format == "mp4" {
@@ -146,68 +364,326 @@ pub fn build_encode_args(params: &EncodeParams, output_path: &str) -> Vec<String
         args.push("+faststart".to_string());
     }
 
+    // 用户自定义的输出选项放在最后，可覆盖上面的默认设置
+    args.extend(extra::output_args(&params.extra_args)?);
+
     // 覆盖已有文件
     args.push("-y".to_string());
 
     // 输出路径
     args.push(output_path.to_string());
 
-    args
+    Ok(args)
 }
 
-/// 检测系统上是否存在支持的硬件编码器（结果通过 OnceLock 缓存）
-fn detect_gpu_encoder() -> Option<String> {
-    let ffmpeg = ffmpeg_bin();
-    let output = Command::new(ffmpeg)
-        .arg("-hide_banner")
-        .arg("-encoders")
-        .output();
+/// 容器封装开销的估计比例，计算目标大小时预留
+const CONTAINER_OVERHEAD: f64 = 0.01;
 
-    if let Ok(output) = output {
-        if let Ok(text) = String::from_utf8(output.stdout) {
-            let lower = text.to_lowercase();
-            if lower.contains("h264_nvenc") || lower.contains("hevc_nvenc") {
-                return Some("nvenc".to_string());
-            }
-            if lower.contains("h264_qsv") || lower.contains("hevc_qsv") {
-                return Some("qsv".to_string());
-            }
-            if lower.contains("h264_vaapi") || lower.contains("hevc_vaapi") {
-                return Some("vaapi".to_string());
-            }
-            if lower.contains("h264_amf") || lower.contains("hevc_amf") {
-                return Some("amf".to_string());
+/// 目标大小模式下允许的最低视频码率（kbps），低于此值画面已不可用
+const MIN_TARGET_VIDEO_KBPS: f64 = 64.0;
+
+/// 根据目标文件大小、时长和音频码率计算视频码率（kbps）
+pub fn target_video_bitrate(size_mb: f64, duration_secs: f64, audio_kbps: u32) -> Result<u32, String> {
+    if duration_secs <= 0.0 {
+        return Err("无法获取视频时长，目标大小模式不可用".to_string());
+    }
+    if size_mb <= 0.0 {
+        return Err("目标大小必须大于 0".to_string());
+    }
+
+    let total_kbps = size_mb * 1_000_000.0 * 8.0 / 1000.0 / duration_secs;
+    let video_kbps = total_kbps * (1.0 - CONTAINER_OVERHEAD) - f64::from(audio_kbps);
+    if video_kbps < MIN_TARGET_VIDEO_KBPS {
+        return Err(format!(
+            "目标大小过小：{size_mb} MB 在 {duration_secs:.0} 秒内只能分配 {video_kbps:.0} kbps 给视频"
+        ));
+    }
+    Ok(video_kbps as u32)
+}
+
+/// 输出超出目标大小时按实际比例修正视频码率，并额外预留 2% 余量
+pub fn corrected_bitrate(bitrate: u32, target_bytes: u64, actual_bytes: u64) -> u32 {
+    let ratio = target_bytes as f64 / actual_bytes.max(1) as f64;
+    (f64::from(bitrate) * ratio * 0.98) as u32
+}
+
+/// 码率控制参数：CRF / 平均码率 / 恒定码率 / 两遍 VBR
+fn push_rate_control_args(
+    args: &mut Vec<String>,
+    params: &EncodeParams,
+    encoder: &str,
+    pass: Option<PassSpec>,
+) -> Result<(), String> {
+    match params.rate_control {
+        RateControl::Crf => args.extend(encoder::quality_args(encoder, params.crf)),
+        RateControl::Abr { bitrate } => {
+            args.extend(encoder::bitrate_mode_args(encoder, BitrateMode::Variable));
+            args.push("-b:v".to_string());
+            args.push(format!("{bitrate}k"));
+        }
+        RateControl::Cbr {
+            bitrate,
+            maxrate,
+            bufsize,
+        } => {
+            let maxrate = maxrate.unwrap_or(bitrate);
+            let bufsize = bufsize.unwrap_or(bitrate * 2);
+            args.extend(encoder::bitrate_mode_args(encoder, BitrateMode::Constant));
+            args.extend([
+                "-b:v".to_string(),
+                format!("{bitrate}k"),
+                "-minrate".to_string(),
+                format!("{bitrate}k"),
+                "-maxrate".to_string(),
+                format!("{maxrate}k"),
+                "-bufsize".to_string(),
+                format!("{bufsize}k"),
+            ]);
+        }
+        RateControl::TwoPass { bitrate } => {
+            args.extend(encoder::bitrate_mode_args(encoder, BitrateMode::Variable));
+            args.push("-b:v".to_string());
+            args.push(format!("{bitrate}k"));
+            if let Some(pass) = pass {
+                push_pass_args(args, encoder, pass);
             }
         }
+        // 目标大小需先由 runner 按时长换算为两遍编码的码率
+        RateControl::TargetSize { .. } => {
+            return Err("目标大小模式尚未换算为视频码率".to_string());
+        }
     }
-
-    None
+    Ok(())
 }
 
-/// 构建 ffprobe 获取视频时长的参数
-pub fn build_probe_duration_args(video_path: &str) -> Vec<String> {
-    vec![
-        "-v".to_string(),
-        "error".to_string(),
-        "-show_entries".to_string(),
-        "format=duration".to_string(),
-        "-of".to_string(),
-        "default=noprint_wrappers=1:nokey=1".to_string(),
-        video_path.to_string(),
-    ]
+/// libx265 不读取 FFmpeg 通用的 `-pass` 选项，由 [`x26x::args`] 合并到 `-x265-params` 中
+fn push_pass_args(args: &mut Vec<String>, encoder: &str, pass: PassSpec) {
+    if encoder == "libx265" {
+        return;
+    }
+    args.push("-pass".to_string());
+    args.push(pass.pass.to_string());
+    args.push("-passlogfile".to_string());
+    args.push(pass.log_prefix.to_string());
 }
 
-/// 构建 ffprobe 获取视频信息的参数
-pub fn build_probe_info_args(video_path: &str) -> Vec<String> {
+/// 构建 ffprobe 获取完整媒体信息（所有流、章节和容器元数据）的参数
+pub fn build_probe_media_args(video_path: &str) -> Vec<String> {
     vec![
         "-v".to_string(),
         "error".to_string(),
-        "-select_streams".to_string(),
-        "v:0".to_string(),
-        "-show_entries".to_string(),
-        "stream=width,height,duration:format=duration,format_name".to_string(),
+        "-show_format".to_string(),
+        "-show_streams".to_string(),
+        "-show_chapters".to_string(),
         "-of".to_string(),
         "json".to_string(),
         video_path.to_string(),
     ]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{AudioSource, AudioTrack, ScaleOptions, Scaler, TimeRange};
+
+    fn params() -> EncodeParams {
+        serde_json::from_value(serde_json::json!({
+            "videoPath": "/videos/in.mkv",
+            "subtitlePath": "/subs/a.ass",
+            "outputDir": "/videos/out",
+            "outputFormat": "mp4",
+            "videoCodec": "libx264",
+            "crf": 23,
+            "subtitleEncoding": "utf8",
+            "subtitleStyle": "default",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_subtitle_filter() {
+        let mut params = params();
+        params.subtitle_path = "C:\\subs\\ep01 [v2], it's.ass".to_string();
+        assert_eq!(
+            build_subtitle_filter(&params, None).to_string(),
+            "subtitles=filename=C\\\\:\\\\\\\\subs\\\\\\\\ep01 \\[v2\\]\\, it\\\\\\'s.ass"
+        );
+
+        // 缩放和帧率转换在字幕之前，截取片段时两端恢复和归零时间戳
+        params.subtitle_path = "/subs/a.srt".to_string();
+        params.subtitle_encoding = "gbk".to_string();
+        params.subtitle_style = "custom".to_string();
+        params.scale = Some(ScaleOptions {
+            width: None,
+            height: Some(720),
+            keep_aspect: true,
+            scaler: Scaler::Lanczos,
+        });
+        params.fps = Some(29.97);
+        assert_eq!(
+            build_subtitle_filter(&params, Some(12.5)).to_string(),
+            "setpts=PTS+12.500/TB,scale=w=-2:h=720:flags=lanczos,fps=30000/1001,\
+             subtitles=filename=/subs/a.srt:charenc=gbk:force_style=PrimaryColour\\\\=&H00FFFFFF,\
+             setpts=PTS-STARTPTS"
+        );
+    }
+
+    #[test]
+    fn test_rate_control_args() {
+        let mut params = params();
+        let render = |params: &EncodeParams, pass: Option<PassSpec>| {
+            let mut args = Vec::new();
+            push_rate_control_args(&mut args, params, "libx264", pass).map(|_| args.join(" "))
+        };
+
+        assert_eq!(render(&params, None).unwrap(), "-crf 23");
+
+        params.rate_control = RateControl::Cbr {
+            bitrate: 6000,
+            maxrate: None,
+            bufsize: Some(3000),
+        };
+        assert_eq!(
+            render(&params, None).unwrap(),
+            "-b:v 6000k -minrate 6000k -maxrate 6000k -bufsize 3000k"
+        );
+
+        params.rate_control = RateControl::TwoPass { bitrate: 2500 };
+        let pass = PassSpec {
+            pass: 2,
+            log_prefix: "ffsub-1",
+        };
+        assert_eq!(
+            render(&params, Some(pass)).unwrap(),
+            "-b:v 2500k -pass 2 -passlogfile ffsub-1"
+        );
+
+        // 目标大小必须先换算为码率
+        params.rate_control = RateControl::TargetSize {
+            size_mb: 100.0,
+            audio_bitrate: None,
+        };
+        assert!(render(&params, None).is_err());
+    }
+
+    #[test]
+    fn test_target_video_bitrate() {
+        // 500 MB / 1234 秒 ≈ 3241 kbps，扣除 1% 封装开销和 128 kbps 音频
+        assert_eq!(target_video_bitrate(500.0, 1234.0, 128), Ok(3081));
+        assert!(target_video_bitrate(1.0, 3600.0, 128).is_err());
+        assert!(target_video_bitrate(500.0, 0.0, 128).is_err());
+        assert_eq!(corrected_bitrate(1000, 100, 110), 890);
+    }
+
+    #[test]
+    fn test_select_encoder() {
+        let mut params = params();
+        params.encoder_choice = EncoderChoice::Software;
+        params.video_codec = "copy".to_string();
+        assert_eq!(select_encoder(&params), "libx264");
+
+        params.encoder_choice = EncoderChoice::Specific {
+            encoder: "hevc_qsv".to_string(),
+        };
+        assert_eq!(select_encoder(&params), "hevc_qsv");
+
+        // 两遍编码的任务自动选择时不使用硬件编码器，指定的编码器不支持时给出说明
+        params.rate_control = RateControl::TwoPass { bitrate: 3000 };
+        assert!(needs_two_pass(&params, "libx264"));
+        assert!(!needs_two_pass(&params, "hevc_qsv"));
+        assert!(two_pass_note(&params, "hevc_qsv").is_some());
+        assert!(two_pass_note(&params, "libx265").is_none());
+        params.encoder_choice = EncoderChoice::Auto;
+        params.video_codec = "libx265".to_string();
+        assert_eq!(select_encoder(&params), "libx265");
+    }
+
+    #[test]
+    fn test_input_and_output_order() {
+        let mut params = params();
+        params.extra_args.input = "-thread_queue_size 512".to_string();
+        params.extra_args.output = "-tag:v hvc1".to_string();
+        params.audio_tracks = vec![AudioTrack {
+            source: AudioSource::Index { index: 2 },
+            default: true,
+            title: None,
+            encode: None,
+        }];
+        params.trim = vec![TimeRange { start: 90.0, end: Some(600.0) }];
+        let joined = build_encode_args(&params, "libx264", "/out.mp4").unwrap().join(" ");
+        assert!(joined.starts_with(
+            "-ss 90.000 -t 510.000 -thread_queue_size 512 -i /videos/in.mkv \
+             -vf setpts=PTS+90.000/TB,subtitles=filename=/subs/a.ass,setpts=PTS-STARTPTS \
+             -map 0:V:0 -map 0:2 -c:v libx264 -crf 23 -preset medium -threads 0 -pix_fmt yuv420p -c:a copy"
+        ));
+        assert!(joined.ends_with("-movflags +faststart -tag:v hvc1 -y /out.mp4"));
+
+        // 两遍编码的第一遍不写音频，输出到空设备
+        let pass1 = PassSpec { pass: 1, log_prefix: "p" };
+        let joined = build_pass_args(&params, "libx264", "/out.mp4", pass1).unwrap().join(" ");
+        assert!(joined.ends_with("-threads 0 -pix_fmt yuv420p -tag:v hvc1 -an -f null -y -"));
+
+        params.extra_args.output = "-map 0".to_string();
+        assert!(build_encode_args(&params, "libx264", "/out.mp4").is_err());
+    }
+
+    #[test]
+    fn test_multi_range_trim_maps_graph_outputs() {
+        let mut params = params();
+        params.trim = vec![
+            TimeRange { start: 0.0, end: Some(10.0) },
+            TimeRange { start: 20.0, end: None },
+        ];
+        params.audio_tracks = vec![AudioTrack {
+            source: AudioSource::Index { index: 1 },
+            default: true,
+            title: None,
+            encode: None,
+        }];
+        let joined = build_encode_args(&params, "libx264", "/out.mp4").unwrap().join(" ");
+        assert!(joined.starts_with("-i /videos/in.mkv -filter_complex "));
+        assert!(joined.contains("[vcat] -map [acat0] -c:v libx264"));
+        assert!(!joined.contains("-ss") && !joined.contains("-vf"));
+    }
+
+    #[test]
+    fn test_vaapi_upload_and_preview() {
+        let mut params = params();
+        let args = build_encode_args(&params, "h264_vaapi", "/out.mp4").unwrap();
+        assert_eq!(
+            &args[..6],
+            [
+                "-vaapi_device",
+                DEFAULT_VAAPI_DEVICE,
+                "-i",
+                "/videos/in.mkv",
+                "-vf",
+                "subtitles=filename=/subs/a.ass,format=nv12,hwupload",
+            ]
+        );
+
+        // 预览片段：设备在 seek 之前，上传在时间戳归零之后，使用最快的速度档位
+        params.vaapi_device = Some("/dev/dri/renderD129".to_string());
+        let clip = ClipRange { start: 1.0, duration: 2.0 };
+        let args = build_preview_args(&params, "hevc_vaapi", clip, "/p.mp4").unwrap();
+        assert_eq!(&args[..6], ["-vaapi_device", "/dev/dri/renderD129", "-ss", "1.000", "-t", "2.000"]);
+        assert!(args[9].ends_with("setpts=PTS-STARTPTS,format=nv12,hwupload"));
+        assert!(args.join(" ").contains("-compression_level 7 -threads 0"));
+    }
+
+    #[test]
+    fn test_clip_range() {
+        assert_eq!(
+            ClipRange::around(3.0, 10.0, 60.0),
+            ClipRange { start: 0.0, duration: 10.0 }
+        );
+        assert_eq!(
+            ClipRange::starting_at(55.0, 10.0, 60.0),
+            ClipRange { start: 50.0, duration: 10.0 }
+        );
+        assert_eq!(
+            ClipRange::starting_at(0.0, 10.0, 4.0),
+            ClipRange { start: 0.0, duration: 4.0 }
+        );
+    }
+}
@@ -0,0 +1,281 @@
+//! 音轨选择、映射与转码
+//!
+//! 未指定音轨时沿用 FFmpeg 的默认选择（只保留一条音轨）；指定后按列表顺序
+//! 生成 `-map`，并为每条输出音轨设置默认标记、标题和转码选项。
+
+use crate::ffmpeg::compat;
+use crate::types::{
+    AudioCodec, AudioEncode, AudioSource, AudioStream, AudioTrack, EncodeParams, MediaInfo,
+};
+
+/// 音频编码格式对应的 FFmpeg 编码器
+pub fn encoder_name(codec: AudioCodec) -> &'static str {
+    match codec {
+        AudioCodec::Aac => "aac",
+        AudioCodec::Opus => "libopus",
+        AudioCodec::Mp3 => "libmp3lame",
+        AudioCodec::Flac => "flac",
+        AudioCodec::Ac3 => "ac3",
+    }
+}
+
+/// 检查转码设置是否在编码器支持的范围内
+pub fn validate_encode(encode: &AudioEncode) -> Result<(), String> {
+    let name = encoder_name(encode.codec);
+    let max_channels = match encode.codec {
+        AudioCodec::Mp3 => 2,
+        AudioCodec::Ac3 => 6,
+        AudioCodec::Aac | AudioCodec::Opus | AudioCodec::Flac => 8,
+    };
+    if let Some(channels) = encode.channels
+        && !(1..=max_channels).contains(&channels)
+    {
+        return Err(format!("{name} 支持 1-{max_channels} 个声道，当前为 {channels}"));
+    }
+
+    if let Some(rate) = encode.sample_rate {
+        let supported: &[u32] = match encode.codec {
+            AudioCodec::Opus => &[8000, 12000, 16000, 24000, 48000],
+            AudioCodec::Mp3 => &[8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000],
+            AudioCodec::Ac3 => &[32000, 44100, 48000],
+            AudioCodec::Aac | AudioCodec::Flac => &[],
+        };
+        if !supported.is_empty() && !supported.contains(&rate) {
+            return Err(format!("{name} 不支持 {rate} Hz 采样率"));
+        }
+        if !(8000..=192_000).contains(&rate) {
+            return Err(format!("采样率超出范围: {rate} Hz"));
+        }
+    }
+
+    if let Some(kbps) = encode.bitrate
+        && !(6..=1536).contains(&kbps)
+    {
+        return Err(format!("音频码率超出范围: {kbps} kbps"));
+    }
+    Ok(())
+}
+
+/// 音轨转码后的码率（kbps）：优先使用指定码率，其次编码器默认值；无损编码返回 `None`
+pub fn encode_bitrate(encode: &AudioEncode) -> Option<u32> {
+    match encode.codec {
+        AudioCodec::Flac => None,
+        codec => encode
+            .bitrate
+            .or_else(|| compat::default_audio_bitrate(encoder_name(codec))),
+    }
+}
+
+/// 按音轨列表从源文件中选出音频流，顺序与列表一致
+///
+/// 按语言选择时取第一条语言匹配且尚未被选中的音轨；列表为空时返回所有音频流。
+pub fn select_streams<'a>(
+    tracks: &[AudioTrack],
+    info: &'a MediaInfo,
+) -> Result<Vec<&'a AudioStream>, String> {
+    if tracks.is_empty() {
+        return Ok(info.audio.iter().collect());
+    }
+
+    let mut selected: Vec<&AudioStream> = Vec::with_capacity(tracks.len());
+    for track in tracks {
+        let stream = match &track.source {
+            AudioSource::Index { index } => info
+                .audio
+                .iter()
+                .find(|a| a.index == *index)
+                .ok_or_else(|| format!("源文件中没有序号为 {index} 的音轨"))?,
+            AudioSource::Language { language } => info
+                .audio
+                .iter()
+                .find(|a| {
+                    a.language
+                        .as_deref()
+                        .is_some_and(|l| l.eq_ignore_ascii_case(language))
+                        && !selected.iter().any(|s| s.index == a.index)
+                })
+                .ok_or_else(|| format!("源文件中没有语言为 {language} 的音轨"))?,
+        };
+        selected.push(stream);
+    }
+    Ok(selected)
+}
+
+/// 将按语言选择的音轨解析为具体的流序号，保证生成的 `-map` 与探测结果一致，
+/// 并检查各音轨的转码设置
+pub fn resolve_tracks(params: &mut EncodeParams, info: &MediaInfo) -> Result<(), String> {
+    for encode in params.audio_tracks.iter().filter_map(|t| t.encode.as_ref()) {
+        validate_encode(encode)?;
+    }
+    let streams = select_streams(&params.audio_tracks, info)?;
+    let indices: Vec<u32> = streams.iter().map(|s| s.index).collect();
+    for (track, index) in params.audio_tracks.iter_mut().zip(indices) {
+        track.source = AudioSource::Index { index };
+    }
+    Ok(())
+}
+
+/// 生成 `-map` 参数：第一条视频流加上列表中的音轨；未指定音轨时返回空列表
+pub fn map_args(tracks: &[AudioTrack]) -> Vec<String> {
+    if tracks.is_empty() {
+        return Vec::new();
+    }
+
+    // `V` 只匹配真正的视频流，排除 MP4/MKV 中作为视频流存储的封面图
+    let mut args: Vec<String> = ["-map", "0:V:0"].map(String::from).to_vec();
+    for specifier in stream_specifiers(tracks) {
+        args.push("-map".to_string());
+        args.push(specifier);
+    }
+    args
+}
+
+/// 各音轨对应的流说明符，如 `0:1`，也可用作滤镜图的输入连接标签
+pub fn stream_specifiers(tracks: &[AudioTrack]) -> Vec<String> {
+    tracks
+        .iter()
+        .map(|t| match &t.source {
+            AudioSource::Index { index } => format!("0:{index}"),
+            AudioSource::Language { language } => format!("0:a:m:language:{language}"),
+        })
+        .collect()
+}
+
+/// FFmpeg 默认会选择的音频流：声道数最多的一条，相同时取第一条
+pub fn default_stream(info: &MediaInfo) -> Option<&AudioStream> {
+    info.audio.iter().rev().max_by_key(|a| a.channels)
+}
+
+/// 生成每条输出音轨的 `-disposition` 和标题元数据参数
+///
+/// 没有音轨标记为默认时，第一条输出音轨设为默认。
+pub fn disposition_args(tracks: &[AudioTrack]) -> Vec<String> {
+    let any_default = tracks.iter().any(|t| t.default);
+    let mut args = Vec::new();
+    for (i, track) in tracks.iter().enumerate() {
+        let default = track.default || (!any_default && i == 0);
+        args.push(format!("-disposition:a:{i}"));
+        args.push(if default { "default" } else { "0" }.to_string());
+        if let Some(title) = &track.title {
+            args.push(format!("-metadata:s:a:{i}"));
+            args.push(format!("title={title}"));
+        }
+    }
+    args
+}
+
+/// 生成各音轨的转码参数，使用 `:a:N` 流说明符覆盖 `-c:a` 的全局设置
+pub fn encode_args(tracks: &[AudioTrack]) -> Vec<String> {
+    let mut args = Vec::new();
+    for (i, track) in tracks.iter().enumerate() {
+        let Some(encode) = &track.encode else {
+            continue;
+        };
+        args.push(format!("-c:a:{i}"));
+        args.push(encoder_name(encode.codec).to_string());
+        if let Some(kbps) = encode_bitrate(encode) {
+            args.push(format!("-b:a:{i}"));
+            args.push(format!("{kbps}k"));
+        }
+        if let Some(channels) = encode.channels {
+            args.push(format!("-ac:a:{i}"));
+            args.push(channels.to_string());
+        }
+        if let Some(rate) = encode.sample_rate {
+            args.push(format!("-ar:a:{i}"));
+            args.push(rate.to_string());
+        }
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(index: u32, language: &str) -> AudioStream {
+        AudioStream {
+            index,
+            codec: "aac".to_string(),
+            language: Some(language.to_string()),
+            ..AudioStream::default()
+        }
+    }
+
+    fn track(source: AudioSource) -> AudioTrack {
+        AudioTrack {
+            source,
+            default: false,
+            title: None,
+            encode: None,
+        }
+    }
+
+    #[test]
+    fn test_select_by_language_and_index() {
+        let info = MediaInfo {
+            audio: vec![stream(1, "eng"), stream(2, "jpn"), stream(3, "jpn")],
+            ..MediaInfo::default()
+        };
+        let language = |l: &str| track(AudioSource::Language { language: l.to_string() });
+
+        let picked = select_streams(&[language("JPN"), language("jpn"), language("eng")], &info)
+            .unwrap()
+            .iter()
+            .map(|s| s.index)
+            .collect::<Vec<_>>();
+        assert_eq!(picked, [2, 3, 1]);
+
+        assert!(select_streams(&[language("fra")], &info).is_err());
+        assert!(select_streams(&[track(AudioSource::Index { index: 0 })], &info).is_err());
+        assert_eq!(select_streams(&[], &info).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_map_and_disposition_args() {
+        assert!(map_args(&[]).is_empty());
+        assert!(disposition_args(&[]).is_empty());
+
+        let tracks = [
+            track(AudioSource::Index { index: 2 }),
+            AudioTrack {
+                source: AudioSource::Index { index: 1 },
+                default: false,
+                title: Some("English Dub".to_string()),
+                encode: None,
+            },
+        ];
+        assert_eq!(map_args(&tracks).join(" "), "-map 0:V:0 -map 0:2 -map 0:1");
+        assert_eq!(
+            disposition_args(&tracks).join(" "),
+            "-disposition:a:0 default -disposition:a:1 0 -metadata:s:a:1 title=English Dub"
+        );
+    }
+
+    #[test]
+    fn test_per_track_encode_args() {
+        let encode = |codec, bitrate, channels, sample_rate| AudioEncode {
+            codec,
+            bitrate,
+            channels,
+            sample_rate,
+        };
+        let mut tracks = vec![
+            track(AudioSource::Index { index: 1 }),
+            track(AudioSource::Index { index: 2 }),
+        ];
+        tracks[1].encode = Some(encode(AudioCodec::Aac, None, Some(2), Some(48000)));
+        assert_eq!(
+            encode_args(&tracks).join(" "),
+            "-c:a:1 aac -b:a:1 192k -ac:a:1 2 -ar:a:1 48000"
+        );
+
+        tracks[0].encode = Some(encode(AudioCodec::Flac, Some(900), None, None));
+        assert!(encode_args(&tracks).join(" ").starts_with("-c:a:0 flac -c:a:1 aac"));
+
+        assert!(validate_encode(&encode(AudioCodec::Opus, Some(96), Some(2), Some(48000))).is_ok());
+        assert!(validate_encode(&encode(AudioCodec::Opus, None, None, Some(44100))).is_err());
+        assert!(validate_encode(&encode(AudioCodec::Mp3, None, Some(6), None)).is_err());
+        assert!(validate_encode(&encode(AudioCodec::Ac3, Some(5000), None, None)).is_err());
+    }
+}
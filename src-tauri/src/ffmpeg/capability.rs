@@ -0,0 +1,382 @@
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::ffmpeg::args::{self, DEFAULT_VAAPI_DEVICE};
+use crate::ffmpeg::encoder::EncoderFamily;
+use crate::types::{EncoderCapabilities, EncoderGroup, EncoderInfo, EncoderProbe};
+
+/// 参与探测的硬件编码器，顺序即自动选择时的优先级
+pub const HW_ENCODERS: &[&str] = &[
+    "h264_nvenc",
+    "hevc_nvenc",
+    "h264_qsv",
+    "hevc_qsv",
+    "h264_vaapi",
+    "hevc_vaapi",
+    "h264_amf",
+    "hevc_amf",
+];
+
+/// 单次测试编码、版本或编码器列表查询的最长等待时间，驱动卡死时终止进程
+const PROBE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 进程内缓存的探测结果，避免每次编码都读取磁盘或启动 FFmpeg
+static CAPABILITIES: Mutex<Option<EncoderCapabilities>> = Mutex::new(None);
+
+/// 获取硬件编码器探测结果：依次使用内存缓存、磁盘缓存（FFmpeg 路径、版本和 VAAPI 设备一致时），
+/// 都不可用时逐个进行测试编码。`refresh` 为真时忽略缓存重新探测。
+///
+/// 探测期间不持有缓存锁，同时发起的探测各自进行，以后完成的结果为准。
+pub fn hw_capabilities(
+    vaapi_device: Option<&str>,
+    refresh: bool,
+) -> Result<EncoderCapabilities, String> {
+    let vaapi_device = vaapi_device.unwrap_or(DEFAULT_VAAPI_DEVICE);
+    if !refresh
+        && let Some(caps) = CAPABILITIES
+            .lock()
+            .map_err(|e| format!("锁竞争错误: {e}"))?
+            .as_ref()
+            .filter(|c| c.vaapi_device == vaapi_device)
+    {
+        return Ok(caps.clone());
+    }
+
+    let ffmpeg_path = resolve_ffmpeg_path();
+    let ffmpeg_version = ffmpeg_version_line()?;
+
+    let caps = match load_disk_cache() {
+        Some(cached)
+            if !refresh
+                && cached.ffmpeg_path == ffmpeg_path
+                && cached.ffmpeg_version == ffmpeg_version
+                && cached.vaapi_device == vaapi_device =>
+        {
+            cached
+        }
+        _ => {
+            let caps = EncoderCapabilities {
+                ffmpeg_path,
+                ffmpeg_version,
+                vaapi_device: vaapi_device.to_string(),
+                probed_at: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0),
+                encoders: probe_hw_encoders(vaapi_device),
+            };
+            save_disk_cache(&caps);
+            caps
+        }
+    };
+    *CAPABILITIES
+        .lock()
+        .map_err(|e| format!("锁竞争错误: {e}"))? = Some(caps.clone());
+    Ok(caps)
+}
+
+/// 在后台线程中探测默认渲染节点上的硬件编码器并填充缓存，应用启动时调用
+pub fn probe_in_background() {
+    std::thread::spawn(|| {
+        if let Err(e) = hw_capabilities(None, false) {
+            log::warn!("硬件编码器探测失败: {e}");
+        }
+    });
+}
+
+/// 内存缓存中经测试编码确认可用的硬件编码器，不启动 FFmpeg
+///
+/// 尚未探测完成时返回空列表（即使用软件编码）；缓存的 VAAPI 结果属于其他渲染节点时不包含 VAAPI 编码器。
+pub fn cached_hw_encoders(vaapi_device: Option<&str>) -> Vec<String> {
+    let vaapi_device = vaapi_device.unwrap_or(DEFAULT_VAAPI_DEVICE);
+    let Ok(guard) = CAPABILITIES.lock() else {
+        return Vec::new();
+    };
+    let Some(caps) = guard.as_ref() else {
+        return Vec::new();
+    };
+    caps.encoders
+        .iter()
+        .filter(|p| p.available)
+        .filter(|p| {
+            caps.vaapi_device == vaapi_device || EncoderFamily::of(&p.encoder) != EncoderFamily::Vaapi
+        })
+        .map(|p| p.encoder.clone())
+        .collect()
+}
+
+/// 对每个候选编码器运行一次极小的 `lavfi` 测试编码，记录是否可用及原因
+fn probe_hw_encoders(vaapi_device: &str) -> Vec<EncoderProbe> {
+    let listed = ffmpeg_encoders().unwrap_or_default();
+
+    HW_ENCODERS
+        .iter()
+        .map(|&encoder| {
+            if !listed.iter().any(|e| e.name == encoder) {
+                return EncoderProbe {
+                    encoder: encoder.to_string(),
+                    available: false,
+                    reason: Some("当前 FFmpeg 未编译该编码器".to_string()),
+                };
+            }
+
+            let result = test_encode(encoder, vaapi_device);
+            log::info!("硬件编码器 {encoder} 测试编码: {result:?}");
+            EncoderProbe {
+                encoder: encoder.to_string(),
+                available: result.is_ok(),
+                reason: result.err(),
+            }
+        })
+        .collect()
+}
+
+/// 编码几帧纯色测试画面到空设备，失败时返回 FFmpeg 最后一行错误输出
+fn test_encode(encoder: &str, vaapi_device: &str) -> Result<(), String> {
+    let output = output_with_timeout(
+        Command::new(args::ffmpeg_bin()).args(build_test_encode_args(encoder, vaapi_device)),
+    )?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(stderr
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("测试编码失败: code={:?}", output.status.code())))
+}
+
+/// 运行命令并收集输出，超过 [`PROBE_TIMEOUT`] 未结束时终止进程
+///
+/// stdout 和 stderr 在各自的线程中持续读取，输出较多时子进程不会因管道写满而阻塞。
+fn output_with_timeout(command: &mut Command) -> Result<Output, String> {
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("无法执行 FFmpeg: {e}"))?;
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let deadline = Instant::now() + PROBE_TIMEOUT;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(50)),
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!(
+                    "FFmpeg 超过 {} 秒未响应，已终止",
+                    PROBE_TIMEOUT.as_secs()
+                ));
+            }
+            Err(e) => return Err(format!("等待 FFmpeg 失败: {e}")),
+        }
+    };
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/// 在后台线程中读取管道直到关闭
+fn drain(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+/// 测试编码参数；VAAPI 需要先上传到指定的渲染节点
+fn build_test_encode_args(encoder: &str, vaapi_device: &str) -> Vec<String> {
+    let mut test_args: Vec<String> = ["-hide_banner", "-v", "error"].map(String::from).to_vec();
+    if EncoderFamily::of(encoder) == EncoderFamily::Vaapi {
+        test_args.push("-vaapi_device".to_string());
+        test_args.push(vaapi_device.to_string());
+    }
+    test_args.extend(
+        ["-f", "lavfi", "-i", "color=c=black:s=256x256:r=25:d=1", "-frames:v", "5"].map(String::from),
+    );
+    if EncoderFamily::of(encoder) == EncoderFamily::Vaapi {
+        test_args.push("-vf".to_string());
+        test_args.push("format=nv12,hwupload".to_string());
+    }
+    test_args.extend(["-c:v", encoder, "-f", "null", "-"].map(String::from));
+    test_args
+}
+
+/// `ffmpeg -encoders` 列表中的一项
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListedEncoder {
+    pub name: String,
+    pub description: String,
+    /// 是否为视频编码器（能力标记首字母为 `V`）
+    pub video: bool,
+    /// 编码格式，取自描述末尾的 `(codec h264)`，缺省时与编码器同名
+    pub codec: String,
+}
+
+/// 运行 `ffmpeg -encoders` 获取当前 FFmpeg 支持的所有编码器
+pub fn ffmpeg_encoders() -> Result<Vec<ListedEncoder>, String> {
+    let output =
+        output_with_timeout(Command::new(args::ffmpeg_bin()).args(["-hide_banner", "-encoders"]))?;
+    Ok(listed_encoders(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// 按编码格式分组列出所有视频编码器，硬件编码器附带测试编码结果
+pub fn list_video_encoders(vaapi_device: Option<&str>) -> Result<Vec<EncoderGroup>, String> {
+    let working: Vec<String> = hw_capabilities(vaapi_device, false)?
+        .encoders
+        .into_iter()
+        .filter(|p| p.available)
+        .map(|p| p.encoder)
+        .collect();
+    let mut groups: Vec<EncoderGroup> = Vec::new();
+
+    for listed in ffmpeg_encoders()?.into_iter().filter(|e| e.video) {
+        let hardware = EncoderFamily::of(&listed.name).is_hardware();
+        let info = EncoderInfo {
+            available: !hardware || working.contains(&listed.name),
+            name: listed.name,
+            description: listed.description,
+            hardware,
+        };
+        match groups.iter_mut().find(|g| g.codec == listed.codec) {
+            Some(group) => group.encoders.push(info),
+            None => groups.push(EncoderGroup {
+                codec: listed.codec,
+                encoders: vec![info],
+            }),
+        }
+    }
+
+    groups.sort_by(|a, b| a.codec.cmp(&b.codec));
+    Ok(groups)
+}
+
+/// 解析 `ffmpeg -encoders` 输出
+///
+/// 列表行格式为 ` V....D h264_nvenc           NVIDIA NVENC H.264 encoder (codec h264)`，
+/// 第一列是能力标记，第二列是名称，其余为描述；分隔线 `------` 之前为图例。
+fn listed_encoders(text: &str) -> Vec<ListedEncoder> {
+    text.lines()
+        .skip_while(|l| !l.trim_start().starts_with("---"))
+        .skip(1)
+        .filter_map(|l| {
+            let mut parts = l.split_whitespace();
+            let flags = parts.next()?;
+            let name = parts.next()?.to_string();
+            let description = parts.collect::<Vec<_>>().join(" ");
+            let codec = description
+                .rsplit_once("(codec ")
+                .and_then(|(_, rest)| rest.strip_suffix(')'))
+                .map(str::to_string)
+                .unwrap_or_else(|| name.clone());
+            Some(ListedEncoder {
+                video: flags.starts_with('V'),
+                name,
+                description,
+                codec,
+            })
+        })
+        .collect()
+}
+
+/// 在 PATH 中查找 FFmpeg 的实际路径，作为缓存键的一部分
+fn resolve_ffmpeg_path() -> String {
+    let bin = args::ffmpeg_bin();
+    std::env::var_os("PATH")
+        .and_then(|paths| {
+            std::env::split_paths(&paths)
+                .map(|dir| dir.join(bin))
+                .find(|p| p.is_file())
+        })
+        .and_then(|p| p.canonicalize().ok())
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|| bin.to_string())
+}
+
+/// `ffmpeg -version` 的第一行，如 `ffmpeg version 7.1 Copyright ...`
+fn ffmpeg_version_line() -> Result<String, String> {
+    let output = output_with_timeout(Command::new(args::ffmpeg_bin()).arg("-version"))?;
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_string())
+}
+
+fn disk_cache_path() -> Option<PathBuf> {
+    let mut dir = dirs_next::cache_dir()?;
+    dir.push("ffsub");
+    dir.push("encoder-capabilities.json");
+    Some(dir)
+}
+
+fn load_disk_cache() -> Option<EncoderCapabilities> {
+    let text = std::fs::read_to_string(disk_cache_path()?).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+fn save_disk_cache(caps: &EncoderCapabilities) {
+    let Some(path) = disk_cache_path() else {
+        return;
+    };
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| {
+            let json = serde_json::to_string_pretty(caps).map_err(std::io::Error::other)?;
+            std::fs::write(&path, json)
+        });
+    if let Err(e) = result {
+        log::warn!("写入编码器探测缓存失败 {}: {e}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listed_encoders() {
+        let text = "Encoders:\n V..... = Video\n A..... = Audio\n ------\n \
+                    V....D libx264              libx264 H.264\n \
+                    V....D h264_nvenc           NVIDIA NVENC H.264 encoder (codec h264)\n \
+                    A....D aac                  AAC (Advanced Audio Coding)\n";
+        let listed = listed_encoders(text);
+        let names: Vec<&str> = listed.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["libx264", "h264_nvenc", "aac"]);
+        assert_eq!(listed[1].codec, "h264");
+        assert_eq!(listed[1].description, "NVIDIA NVENC H.264 encoder (codec h264)");
+        assert!(listed[1].video);
+        assert_eq!(listed[2].codec, "aac");
+        assert!(!listed[2].video);
+    }
+
+    #[test]
+    fn test_vaapi_test_encode_uploads() {
+        let joined = build_test_encode_args("hevc_vaapi", "/dev/dri/renderD129").join(" ");
+        assert!(joined.contains("-vaapi_device /dev/dri/renderD129 -f lavfi"));
+        assert!(joined.ends_with("-vf format=nv12,hwupload -c:v hevc_vaapi -f null -"));
+        assert!(
+            !build_test_encode_args("h264_nvenc", DEFAULT_VAAPI_DEVICE)
+                .join(" ")
+                .contains("hwupload")
+        );
+    }
+}
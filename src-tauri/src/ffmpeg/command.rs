@@ -0,0 +1,170 @@
+//! 把 FFmpeg 参数列表转为可复制执行的命令行和批处理脚本
+//!
+//! POSIX shell 使用单引号（内部的 `'` 写作 `'\''`），PowerShell 使用单引号（内部的 `'` 写作 `''`），
+//! 两者在单引号内都不做任何展开。
+
+use crate::types::{CommandLine, ScriptShell};
+
+/// 脚本和命令行中使用的 FFmpeg 程序名，由 PATH 解析
+pub const FFMPEG: &str = "ffmpeg";
+
+/// 无需加引号的字符
+fn is_plain(arg: &str) -> bool {
+    !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-+=/.,:%@^".contains(c))
+}
+
+pub fn quote_posix(arg: &str) -> String {
+    if is_plain(arg) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+pub fn quote_powershell(arg: &str) -> String {
+    // 裸参数中的 `,` 会组成数组，开头的 `@` 会被当作展开运算符
+    if is_plain(arg) && !arg.contains(',') && !arg.starts_with('@') {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "''"))
+    }
+}
+
+/// 完整的命令行：`argv` 第一项为程序名
+pub fn command_line(args: &[String]) -> CommandLine {
+    let argv: Vec<String> = std::iter::once(FFMPEG.to_string())
+        .chain(args.iter().cloned())
+        .collect();
+    let posix = argv
+        .iter()
+        .map(|a| quote_posix(a))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let powershell = format!(
+        "& {}",
+        argv.iter()
+            .map(|a| quote_powershell(a))
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+    CommandLine {
+        argv,
+        posix,
+        powershell,
+    }
+}
+
+/// 脚本文件应使用的扩展名（不含点号）
+pub fn extension(shell: ScriptShell) -> &'static str {
+    match shell {
+        ScriptShell::Posix => "sh",
+        ScriptShell::PowerShell => "ps1",
+    }
+}
+
+/// 批处理脚本中的一个任务
+pub struct ScriptJob {
+    /// 写在任务前的注释，如源文件路径
+    pub title: String,
+    /// 输出文件所在目录，执行前创建（文件名模板可以包含子目录）
+    pub output_dir: Option<String>,
+    /// 依次执行的各遍 FFmpeg 参数（不含程序名）
+    pub passes: Vec<Vec<String>>,
+    pub notes: Vec<String>,
+}
+
+/// 生成批处理脚本，任一命令失败时立即退出
+pub fn script(shell: ScriptShell, jobs: &[ScriptJob]) -> String {
+    let mut lines: Vec<String> = match shell {
+        ScriptShell::Posix => vec!["#!/bin/sh".to_string(), "set -e".to_string()],
+        ScriptShell::PowerShell => vec!["$ErrorActionPreference = 'Stop'".to_string()],
+    };
+
+    for (i, job) in jobs.iter().enumerate() {
+        lines.push(String::new());
+        // 注释中不能出现换行
+        let comment = |text: &str| format!("# {}", text.replace(['\r', '\n'], " "));
+        lines.push(comment(&format!(
+            "[{}/{}] {}",
+            i + 1,
+            jobs.len(),
+            job.title
+        )));
+        lines.extend(job.notes.iter().map(|n| comment(n)));
+        if let Some(dir) = &job.output_dir {
+            lines.push(match shell {
+                ScriptShell::Posix => format!("mkdir -p {}", quote_posix(dir)),
+                ScriptShell::PowerShell => format!(
+                    "New-Item -ItemType Directory -Force -Path {} | Out-Null",
+                    quote_powershell(dir)
+                ),
+            });
+        }
+        for pass in &job.passes {
+            let command = command_line(pass);
+            match shell {
+                ScriptShell::Posix => lines.push(command.posix),
+                ScriptShell::PowerShell => {
+                    lines.push(command.powershell);
+                    // 外部程序失败不会触发 ErrorActionPreference，需检查退出码
+                    lines.push("if ($LASTEXITCODE -ne 0) { exit $LASTEXITCODE }".to_string());
+                }
+            }
+        }
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quoting() {
+        let args: Vec<String> = [
+            "-i",
+            "/videos/it's a show.mkv",
+            "-vf",
+            "subtitles=a.ass,setpts=PTS-STARTPTS",
+            "-c:v",
+        ]
+        .map(String::from)
+        .to_vec();
+        let line = command_line(&args);
+        assert_eq!(line.argv[0], "ffmpeg");
+        assert_eq!(
+            line.posix,
+            "ffmpeg -i '/videos/it'\\''s a show.mkv' -vf subtitles=a.ass,setpts=PTS-STARTPTS -c:v"
+        );
+        assert_eq!(
+            line.powershell,
+            "& ffmpeg -i '/videos/it''s a show.mkv' -vf 'subtitles=a.ass,setpts=PTS-STARTPTS' -c:v"
+        );
+        assert_eq!(quote_posix(""), "''");
+        assert_eq!(quote_posix("$HOME"), "'$HOME'");
+    }
+
+    #[test]
+    fn test_script() {
+        let job = ScriptJob {
+            title: "in.mkv".to_string(),
+            output_dir: Some("out/2024".to_string()),
+            passes: vec![vec!["-i".to_string(), "in.mkv".to_string()]],
+            notes: Vec::new(),
+        };
+        let posix = script(ScriptShell::Posix, std::slice::from_ref(&job));
+        assert_eq!(
+            posix,
+            "#!/bin/sh\nset -e\n\n# [1/1] in.mkv\nmkdir -p out/2024\nffmpeg -i in.mkv\n"
+        );
+        let powershell = script(ScriptShell::PowerShell, &[job]);
+        assert!(
+            powershell
+                .ends_with("& ffmpeg -i in.mkv\nif ($LASTEXITCODE -ne 0) { exit $LASTEXITCODE }\n")
+        );
+    }
+}
@@ -0,0 +1,397 @@
+//! 编码格式与容器的兼容性检查
+//!
+//! 在启动 FFmpeg 之前检查视频编码、音频处理方式和字幕处理方式能否写入目标容器，
+//! 给出修改建议；源音频无法直接复制时可自动选择容器支持的音频编码器转码。
+
+use crate::ffmpeg::audio;
+use crate::ffmpeg::encoder::EncoderFamily;
+use crate::types::{CompatIssue, CompatReport, EncodeParams, IssueSeverity};
+
+/// 源文件中各类流的编码格式（ffprobe `codec_name`）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceCodecs {
+    pub video: Vec<String>,
+    pub audio: Vec<String>,
+    pub subtitle: Vec<String>,
+}
+
+/// 容器支持的编码格式；`None` 表示不限制
+struct ContainerSpec {
+    video: Option<&'static [&'static str]>,
+    audio: Option<&'static [&'static str]>,
+    /// 可写入的源字幕格式（文本字幕由 FFmpeg 转换为容器的默认字幕格式）
+    subtitle: Option<&'static [&'static str]>,
+    /// 源音频不兼容时转码使用的音频编码器
+    fallback_audio: &'static str,
+}
+
+/// 文本字幕格式，可转换为 MP4/MOV 的 `mov_text` 或 WebM 的 `webvtt`；位图字幕（PGS、DVD）不能转换
+const TEXT_SUBTITLES: &[&str] = &["subrip", "ass", "ssa", "mov_text", "webvtt", "text"];
+
+/// 兼容性矩阵；MP4 不接受 FLAC（旧版 FFmpeg 视为实验性）、DTS、TrueHD 和 PCM
+fn container_spec(format: &str) -> Option<ContainerSpec> {
+    let spec = match format {
+        "mp4" => ContainerSpec {
+            video: Some(&["h264", "hevc", "av1", "vp9", "mpeg4"]),
+            audio: Some(&["aac", "mp3", "ac3", "eac3", "opus", "alac"]),
+            subtitle: Some(TEXT_SUBTITLES),
+            fallback_audio: "aac",
+        },
+        "mov" => ContainerSpec {
+            video: Some(&["h264", "hevc", "mpeg4", "prores"]),
+            audio: Some(&[
+                "aac",
+                "mp3",
+                "ac3",
+                "eac3",
+                "alac",
+                "pcm_s16le",
+                "pcm_s24le",
+            ]),
+            subtitle: Some(TEXT_SUBTITLES),
+            fallback_audio: "aac",
+        },
+        "mkv" => ContainerSpec {
+            video: None,
+            audio: None,
+            subtitle: None,
+            fallback_audio: "aac",
+        },
+        "webm" => ContainerSpec {
+            video: Some(&["vp8", "vp9", "av1"]),
+            audio: Some(&["opus", "vorbis"]),
+            subtitle: Some(TEXT_SUBTITLES),
+            fallback_audio: "libopus",
+        },
+        "avi" => ContainerSpec {
+            video: Some(&["h264", "mpeg4", "mjpeg"]),
+            audio: Some(&["mp3", "mp2", "ac3", "pcm_s16le"]),
+            subtitle: Some(&[]),
+            fallback_audio: "libmp3lame",
+        },
+        _ => return None,
+    };
+    Some(spec)
+}
+
+/// 编码器输出的编码格式；无法识别时返回 `None`，不做检查
+pub fn encoder_codec(encoder: &str) -> Option<&'static str> {
+    match encoder {
+        "libx264" => Some("h264"),
+        "libx265" => Some("hevc"),
+        "libsvtav1" | "libaom-av1" | "librav1e" => Some("av1"),
+        "libvpx-vp9" => Some("vp9"),
+        "libvpx" => Some("vp8"),
+        "mpeg4" | "libxvid" => Some("mpeg4"),
+        "aac" | "libfdk_aac" => Some("aac"),
+        "libopus" => Some("opus"),
+        "libvorbis" => Some("vorbis"),
+        "libmp3lame" => Some("mp3"),
+        "ac3" => Some("ac3"),
+        "flac" => Some("flac"),
+        _ if EncoderFamily::of(encoder).is_hardware() => {
+            match encoder.split_once('_').map(|(codec, _)| codec) {
+                Some("h264") => Some("h264"),
+                Some("hevc") => Some("hevc"),
+                Some("av1") => Some("av1"),
+                Some("vp9") => Some("vp9"),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// 音频编码器的默认码率（kbps），无损编码器返回 `None`
+pub fn default_audio_bitrate(encoder: &str) -> Option<u32> {
+    match encoder {
+        "libopus" | "libvorbis" => Some(128),
+        "aac" | "libfdk_aac" | "libmp3lame" => Some(192),
+        "ac3" => Some(384),
+        _ => None,
+    }
+}
+
+/// 检查任务参数与目标容器是否兼容
+///
+/// 返回的报告中 `audio_codec` 为自动选择的音频编码器（仅在需要转码时设置）。
+pub fn check(params: &EncodeParams, encoder: &str, source: &SourceCodecs) -> CompatReport {
+    let mut report = CompatReport::default();
+    let format = params.output_format.as_str();
+
+    let Some(spec) = container_spec(format) else {
+        report.issues.push(issue(
+            IssueSeverity::Error,
+            format!("不支持的输出格式: {format}"),
+            Some("请选择 MP4、MKV、MOV、WebM 或 AVI".to_string()),
+        ));
+        return report;
+    };
+
+    if params.video_codec == "copy" {
+        report.issues.push(issue(
+            IssueSeverity::Warning,
+            "烧录字幕需要重新编码视频，无法直接复制视频流".to_string(),
+            Some(format!("将使用 {encoder} 编码")),
+        ));
+    }
+
+    if let (Some(allowed), Some(codec)) = (spec.video, encoder_codec(encoder))
+        && !allowed.contains(&codec)
+    {
+        report.issues.push(issue(
+            IssueSeverity::Error,
+            format!(
+                "{} 容器不支持 {codec} 视频（编码器 {encoder}）",
+                format.to_uppercase()
+            ),
+            Some(format!(
+                "改用 {} 编码，或选择 MKV 容器",
+                allowed.join(" / ")
+            )),
+        ));
+    }
+
+    // 单独设置了转码的音轨
+    for encode in params.audio_tracks.iter().filter_map(|t| t.encode) {
+        let audio_encoder = audio::encoder_name(encode.codec);
+        if let (Some(allowed), Some(codec)) = (spec.audio, encoder_codec(audio_encoder))
+            && !allowed.contains(&codec)
+        {
+            report.issues.push(issue(
+                IssueSeverity::Error,
+                format!("{} 容器不支持 {codec} 音频", format.to_uppercase()),
+                Some(format!("改用 {} 音频编码器", spec.fallback_audio)),
+            ));
+        }
+    }
+
+    // 其余音轨：`source.audio` 只包含未单独设置转码的音轨
+    match &params.audio_codec {
+        Some(audio_encoder) => {
+            if let (Some(allowed), Some(codec)) = (spec.audio, encoder_codec(audio_encoder))
+                && !allowed.contains(&codec)
+            {
+                report.issues.push(issue(
+                    IssueSeverity::Error,
+                    format!("{} 容器不支持 {codec} 音频", format.to_uppercase()),
+                    Some(format!("改用 {} 音频编码器", spec.fallback_audio)),
+                ));
+            }
+        }
+        None => {
+            let incompatible: Vec<&str> = match spec.audio {
+                Some(allowed) => source
+                    .audio
+                    .iter()
+                    .map(String::as_str)
+                    .filter(|c| !allowed.contains(c))
+                    .collect(),
+                None => Vec::new(),
+            };
+            // 音频滤镜（响度标准化、多段剪辑的截取拼接）无法与流复制同时使用
+            let filtered = if params.loudnorm.is_some() {
+                Some("响度标准化")
+            } else if params.trim.len() > 1 {
+                Some("多段剪辑")
+            } else {
+                None
+            };
+            if let Some(reason) = filtered
+                && !source.audio.is_empty()
+            {
+                report.issues.push(issue(
+                    IssueSeverity::Warning,
+                    format!("{reason}需要重新编码音频"),
+                    Some(format!("音频将转码为 {}", spec.fallback_audio)),
+                ));
+                report.audio_codec = Some(spec.fallback_audio.to_string());
+            } else if !incompatible.is_empty() {
+                let message = format!(
+                    "{} 容器无法直接复制 {} 音频",
+                    format.to_uppercase(),
+                    incompatible.join(" / ")
+                );
+                if params.auto_transcode_audio {
+                    report.issues.push(issue(
+                        IssueSeverity::Warning,
+                        message,
+                        Some(format!("音频将转码为 {}", spec.fallback_audio)),
+                    ));
+                    report.audio_codec = Some(spec.fallback_audio.to_string());
+                } else {
+                    report.issues.push(issue(
+                        IssueSeverity::Error,
+                        message,
+                        Some(format!(
+                            "启用音频自动转码（{}），或选择 MKV 容器",
+                            spec.fallback_audio
+                        )),
+                    ));
+                }
+            }
+        }
+    }
+
+    // 指定了音轨或多段剪辑时使用显式 `-map`，内嵌字幕不会被选中；否则 FFmpeg 默认保留一条内嵌字幕
+    let explicit_map = !params.audio_tracks.is_empty() || params.trim.len() > 1;
+    if explicit_map && !source.subtitle.is_empty() {
+        report.issues.push(issue(
+            IssueSeverity::Warning,
+            format!("源文件中的 {} 条内嵌字幕不会写入输出", source.subtitle.len()),
+            Some("外挂字幕已烧录进画面".to_string()),
+        ));
+    } else if let Some(allowed) = spec.subtitle {
+        let incompatible: Vec<&str> = source
+            .subtitle
+            .iter()
+            .map(String::as_str)
+            .filter(|c| !allowed.contains(c))
+            .collect();
+        if !incompatible.is_empty() {
+            report.issues.push(issue(
+                IssueSeverity::Warning,
+                format!(
+                    "{} 容器无法写入 {} 字幕，内嵌字幕不会写入输出",
+                    format.to_uppercase(),
+                    incompatible.join(" / ")
+                ),
+                Some("选择 MKV 容器可保留内嵌字幕".to_string()),
+            ));
+            report.drop_subtitles = true;
+        }
+    }
+
+    report
+}
+
+fn issue(severity: IssueSeverity, message: String, suggestion: Option<String>) -> CompatIssue {
+    CompatIssue {
+        severity,
+        message,
+        suggestion,
+    }
+}
+
+/// 问题描述及修改建议，用于错误信息
+fn describe(issue: &CompatIssue) -> String {
+    match &issue.suggestion {
+        Some(suggestion) => format!("{}（建议：{suggestion}）", issue.message),
+        None => issue.message.clone(),
+    }
+}
+
+/// 检查兼容性并应用自动修正：存在错误时返回所有错误及建议，否则返回警告列表
+pub fn resolve(
+    params: &mut EncodeParams,
+    encoder: &str,
+    source: &SourceCodecs,
+) -> Result<Vec<CompatIssue>, String> {
+    let report = check(params, encoder, source);
+
+    let errors: Vec<String> = report
+        .issues
+        .iter()
+        .filter(|i| i.severity == IssueSeverity::Error)
+        .map(describe)
+        .collect();
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+
+    if report.audio_codec.is_some() {
+        params.audio_codec = report.audio_codec;
+    }
+    params.drop_subtitles = report.drop_subtitles;
+    Ok(report.issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(format: &str, codec: &str) -> EncodeParams {
+        serde_json::from_value(serde_json::json!({
+            "videoPath": "/videos/in.mkv",
+            "subtitlePath": "/subs/a.ass",
+            "outputDir": "/videos/out",
+            "outputFormat": format,
+            "videoCodec": codec,
+            "crf": 23,
+            "subtitleEncoding": "utf8",
+            "subtitleStyle": "default",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_embedded_subtitles() {
+        let mut source = source(&["aac"]);
+        source.subtitle = vec!["subrip".to_string()];
+        let report = check(&params("mkv", "libx264"), "libx264", &source);
+        assert!(report.issues.is_empty() && !report.drop_subtitles);
+        let report = check(&params("mp4", "libx264"), "libx264", &source);
+        assert!(report.issues.is_empty() && !report.drop_subtitles);
+
+        source.subtitle.push("hdmv_pgs_subtitle".to_string());
+        let report = check(&params("mp4", "libx264"), "libx264", &source);
+        assert_eq!(report.issues.len(), 1);
+        assert!(report.drop_subtitles);
+    }
+
+    fn source(audio: &[&str]) -> SourceCodecs {
+        SourceCodecs {
+            video: vec!["h264".to_string()],
+            audio: audio.iter().map(|s| s.to_string()).collect(),
+            subtitle: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_video_codec_against_container() {
+        let report = check(&params("webm", "libx264"), "libx264", &source(&["opus"]));
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].severity, IssueSeverity::Error);
+        assert!(report.issues[0].message.contains("h264"));
+
+        let report = check(
+            &params("webm", "libsvtav1"),
+            "libsvtav1",
+            &source(&["opus"]),
+        );
+        assert!(report.issues.is_empty());
+        // 硬件编码器按编码格式检查
+        let report = check(&params("mp4", "libx265"), "hevc_nvenc", &source(&["aac"]));
+        assert!(report.issues.is_empty());
+    }
+
+    #[test]
+    fn test_incompatible_audio_is_transcoded() {
+        let mut p = params("mp4", "libx264");
+        let warnings = resolve(&mut p, "libx264", &source(&["aac", "truehd"])).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(p.audio_codec.as_deref(), Some("aac"));
+
+        let mut p = params("webm", "libvpx-vp9");
+        resolve(&mut p, "libvpx-vp9", &source(&["aac"])).unwrap();
+        assert_eq!(p.audio_codec.as_deref(), Some("libopus"));
+
+        // MKV 接受任何音频，保持复制
+        let mut p = params("mkv", "libx264");
+        resolve(&mut p, "libx264", &source(&["dts", "pcm_s24le"])).unwrap();
+        assert_eq!(p.audio_codec, None);
+    }
+
+    #[test]
+    fn test_strict_audio_copy_reports_fix() {
+        let mut p = params("mp4", "libx264");
+        p.auto_transcode_audio = false;
+        let err = resolve(&mut p, "libx264", &source(&["dts"])).unwrap_err();
+        assert!(err.contains("dts"));
+        assert!(err.contains("MKV"));
+
+        let mut p = params("webm", "libvpx-vp9");
+        p.audio_codec = Some("aac".to_string());
+        assert!(resolve(&mut p, "libvpx-vp9", &source(&["opus"])).is_err());
+    }
+}
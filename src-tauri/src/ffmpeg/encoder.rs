@@ -0,0 +1,353 @@
+use crate::types::SpeedPreset;
+
+/// 编码器家族，决定质量、码率控制和速度预设使用哪套 FFmpeg 选项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncoderFamily {
+    /// libx264 / libx265 等沿用 CRF/preset 的软件编码器
+    Software,
+    /// SVT-AV1：`-crf` 0-63，`-preset` 0-13
+    SvtAv1,
+    /// libaom AV1：`-crf` 0-63 配合 `-b:v 0`，`-cpu-used` 0-8
+    Aom,
+    /// rav1e：`-qp` 0-255，`-speed` 0-10
+    Rav1e,
+    /// libvpx VP9：`-crf` 0-63 配合 `-b:v 0`，`-cpu-used` 0-5
+    Vpx,
+    Nvenc,
+    Qsv,
+    Vaapi,
+    Amf,
+}
+
+impl EncoderFamily {
+    pub fn of(encoder: &str) -> Self {
+        if encoder.ends_with("_nvenc") {
+            Self::Nvenc
+        } else if encoder.ends_with("_qsv") {
+            Self::Qsv
+        } else if encoder.ends_with("_vaapi") {
+            Self::Vaapi
+        } else if encoder.ends_with("_amf") {
+            Self::Amf
+        } else {
+            match encoder {
+                "libsvtav1" => Self::SvtAv1,
+                "libaom-av1" => Self::Aom,
+                "librav1e" => Self::Rav1e,
+                "libvpx-vp9" => Self::Vpx,
+                _ => Self::Software,
+            }
+        }
+    }
+
+    pub fn is_hardware(self) -> bool {
+        matches!(self, Self::Nvenc | Self::Qsv | Self::Vaapi | Self::Amf)
+    }
+
+    /// 是否支持 FFmpeg 的 `-pass` 两遍编码（硬件编码器和 SVT-AV1 不支持）
+    pub fn supports_two_pass(self) -> bool {
+        matches!(self, Self::Software | Self::Aom | Self::Rav1e | Self::Vpx)
+    }
+}
+
+/// 码率模式下各硬件编码器需要显式切换的码率控制方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitrateMode {
+    /// 可变码率（ABR / 两遍 VBR）
+    Variable,
+    /// 恒定码率
+    Constant,
+}
+
+/// 将 CRF 等效的质量值（0-51，越小越好）转换为对应编码器的质量选项
+///
+/// libx264/libx265 保留 0（无损）；各硬件编码器的量化参数与 x264 CRF 的刻度大致相同，
+/// 直接沿用数值并限制在 1-51；AV1/VP9 编码器的刻度更大，按比例换算到各自的范围。
+pub fn quality_args(encoder: &str, quality: i32) -> Vec<String> {
+    let crf = quality.clamp(0, 51).to_string();
+    let quality = quality.clamp(1, 51);
+    let q = quality.to_string();
+    let q63 = rescale_quality(quality, 63).to_string();
+    let q255 = rescale_quality(quality, 255).to_string();
+    let args: Vec<&str> = match EncoderFamily::of(encoder) {
+        EncoderFamily::Software => vec!["-crf", &crf],
+        EncoderFamily::SvtAv1 => vec!["-crf", &q63],
+        // libaom/libvpx 的恒定质量模式需要 `-b:v 0`，否则 CRF 只作为码率上限内的质量下限
+        EncoderFamily::Aom | EncoderFamily::Vpx => vec!["-crf", &q63, "-b:v", "0"],
+        EncoderFamily::Rav1e => vec!["-qp", &q255],
+        // NVENC：VBR + 目标质量，`-b:v 0` 解除默认码率上限
+        EncoderFamily::Nvenc => vec!["-rc", "vbr", "-cq", &q, "-b:v", "0"],
+        // QSV：ICQ 模式下使用 global_quality
+        EncoderFamily::Qsv => vec!["-global_quality", &q],
+        // VAAPI：恒定量化参数
+        EncoderFamily::Vaapi => vec!["-rc_mode", "CQP", "-qp", &q],
+        // AMF：恒定量化参数，I/P（H.264 还有 B）帧分别指定
+        EncoderFamily::Amf if encoder.starts_with("h264") => {
+            vec!["-rc", "cqp", "-qp_i", &q, "-qp_p", &q, "-qp_b", &q]
+        }
+        EncoderFamily::Amf => vec!["-rc", "cqp", "-qp_i", &q, "-qp_p", &q],
+    };
+    args.into_iter().map(String::from).collect()
+}
+
+/// 码率模式（ABR/CBR/两遍）下，在 `-b:v` 之外需要追加的码率控制选项
+pub fn bitrate_mode_args(encoder: &str, mode: BitrateMode) -> Vec<String> {
+    let args: &[&str] = match (EncoderFamily::of(encoder), mode) {
+        (
+            EncoderFamily::Software
+            | EncoderFamily::SvtAv1
+            | EncoderFamily::Aom
+            | EncoderFamily::Rav1e
+            | EncoderFamily::Vpx
+            | EncoderFamily::Qsv,
+            _,
+        ) => &[],
+        (EncoderFamily::Nvenc, BitrateMode::Variable) => &["-rc", "vbr"],
+        (EncoderFamily::Nvenc, BitrateMode::Constant) => &["-rc", "cbr"],
+        (EncoderFamily::Vaapi, BitrateMode::Variable) => &["-rc_mode", "VBR"],
+        (EncoderFamily::Vaapi, BitrateMode::Constant) => &["-rc_mode", "CBR"],
+        (EncoderFamily::Amf, BitrateMode::Variable) => &["-rc", "vbr_peak"],
+        (EncoderFamily::Amf, BitrateMode::Constant) => &["-rc", "cbr"],
+    };
+    args.iter().map(|s| s.to_string()).collect()
+}
+
+/// 将统一的速度档位转换为对应编码器的预设选项
+pub fn speed_args(encoder: &str, speed: SpeedPreset) -> Vec<String> {
+    let family = EncoderFamily::of(encoder);
+    let (option, value) = match family {
+        EncoderFamily::Software | EncoderFamily::Qsv => (
+            "-preset",
+            match speed {
+                SpeedPreset::Fastest => "veryfast",
+                SpeedPreset::Fast => "faster",
+                SpeedPreset::Balanced => "medium",
+                SpeedPreset::Quality => "slow",
+                SpeedPreset::Best => "veryslow",
+            },
+        ),
+        // NVENC 新版预设 p1（最快）到 p7（最慢）
+        EncoderFamily::Nvenc => (
+            "-preset",
+            match speed {
+                SpeedPreset::Fastest => "p1",
+                SpeedPreset::Fast => "p3",
+                SpeedPreset::Balanced => "p4",
+                SpeedPreset::Quality => "p6",
+                SpeedPreset::Best => "p7",
+            },
+        ),
+        // VAAPI 的 compression_level 对应驱动的 quality level，数值越小质量越高
+        EncoderFamily::Vaapi => (
+            "-compression_level",
+            match speed {
+                SpeedPreset::Fastest => "7",
+                SpeedPreset::Fast => "6",
+                SpeedPreset::Balanced => "4",
+                SpeedPreset::Quality => "2",
+                SpeedPreset::Best => "1",
+            },
+        ),
+        EncoderFamily::Amf => (
+            "-quality",
+            match speed {
+                SpeedPreset::Fastest | SpeedPreset::Fast => "speed",
+                SpeedPreset::Balanced => "balanced",
+                SpeedPreset::Quality | SpeedPreset::Best => "quality",
+            },
+        ),
+        // SVT-AV1 预设 0（最慢）到 13（最快）
+        EncoderFamily::SvtAv1 => (
+            "-preset",
+            match speed {
+                SpeedPreset::Fastest => "12",
+                SpeedPreset::Fast => "10",
+                SpeedPreset::Balanced => "8",
+                SpeedPreset::Quality => "6",
+                SpeedPreset::Best => "4",
+            },
+        ),
+        EncoderFamily::Aom => (
+            "-cpu-used",
+            match speed {
+                SpeedPreset::Fastest => "8",
+                SpeedPreset::Fast => "6",
+                SpeedPreset::Balanced => "4",
+                SpeedPreset::Quality => "2",
+                SpeedPreset::Best => "1",
+            },
+        ),
+        EncoderFamily::Rav1e => (
+            "-speed",
+            match speed {
+                SpeedPreset::Fastest => "10",
+                SpeedPreset::Fast => "8",
+                SpeedPreset::Balanced => "6",
+                SpeedPreset::Quality => "4",
+                SpeedPreset::Best => "2",
+            },
+        ),
+        // libvpx 在 `-deadline good` 下 cpu-used 取 0-5
+        EncoderFamily::Vpx => (
+            "-cpu-used",
+            match speed {
+                SpeedPreset::Fastest => "5",
+                SpeedPreset::Fast => "4",
+                SpeedPreset::Balanced => "2",
+                SpeedPreset::Quality => "1",
+                SpeedPreset::Best => "0",
+            },
+        ),
+    };
+
+    let mut args = vec![option.to_string(), value.to_string()];
+    match family {
+        // libaom/libvpx 默认单线程处理行，开启 row-mt 才能利用多核
+        EncoderFamily::Aom => args.extend(["-row-mt", "1"].map(String::from)),
+        EncoderFamily::Vpx => args.extend(["-deadline", "good", "-row-mt", "1"].map(String::from)),
+        _ => {}
+    }
+    args
+}
+
+/// 将 1-51 的 CRF 等效质量值按比例换算到 `0..=max` 的刻度
+fn rescale_quality(quality: i32, max: i32) -> i32 {
+    (quality * max + 25) / 51
+}
+
+/// 硬件编码器对应的软件编码器，用于初始化失败后的回退
+pub fn software_equivalent(encoder: &str) -> Option<&'static str> {
+    if !EncoderFamily::of(encoder).is_hardware() {
+        return None;
+    }
+    match encoder.split_once('_').map(|(codec, _)| codec) {
+        Some("h264") => Some("libx264"),
+        Some("hevc") => Some("libx265"),
+        Some("av1") => Some("libsvtav1"),
+        Some("vp9") => Some("libvpx-vp9"),
+        _ => None,
+    }
+}
+
+/// FFmpeg 输出中表示硬件编码器初始化失败的特征文本（小写匹配）
+const HW_INIT_FAILURE_PATTERNS: &[&str] = &[
+    // NVENC：缺少驱动/GPU、API 版本不匹配、消费级显卡并发会话数用尽
+    "cannot load libcuda",
+    "cannot load nvcuda",
+    "no nvenc capable devices found",
+    "no capable devices found",
+    "driver does not support the required nvenc api version",
+    "openencodesessionex failed",
+    "cuinit(0) failed",
+    // QSV
+    "error initializing an internal mfx session",
+    "error creating a mfx session",
+    "failed to create a qsv device",
+    // VAAPI
+    "failed to initialise vaapi connection",
+    "failed to create a vaapi device",
+    "no usable encoding entrypoint found",
+    // AMF
+    "amf failed to initialise",
+    "failed to load amf",
+    "dll amfrt",
+    // 通用：硬件设备创建失败（编码器打开失败也可能是参数错误，不在此列）
+    "device creation failed",
+];
+
+/// 判断失败输出是否为硬件编码器初始化失败，返回命中的那一行作为原因
+pub fn hw_init_failure(log_tail: &[String]) -> Option<String> {
+    log_tail
+        .iter()
+        .find(|line| {
+            let lower = line.to_lowercase();
+            HW_INIT_FAILURE_PATTERNS.iter().any(|p| lower.contains(p))
+        })
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_software_equivalent() {
+        assert_eq!(software_equivalent("h264_nvenc"), Some("libx264"));
+        assert_eq!(software_equivalent("hevc_vaapi"), Some("libx265"));
+        assert_eq!(software_equivalent("av1_nvenc"), Some("libsvtav1"));
+        assert_eq!(software_equivalent("libx264"), None);
+    }
+
+    #[test]
+    fn test_hardware_mappings() {
+        assert_eq!(quality_args("h264_qsv", 24), ["-global_quality", "24"]);
+        assert_eq!(quality_args("hevc_nvenc", 70), ["-rc", "vbr", "-cq", "51", "-b:v", "0"]);
+        assert_eq!(quality_args("h264_vaapi", 24), ["-rc_mode", "CQP", "-qp", "24"]);
+        assert_eq!(
+            quality_args("h264_amf", 24),
+            ["-rc", "cqp", "-qp_i", "24", "-qp_p", "24", "-qp_b", "24"]
+        );
+        assert_eq!(
+            quality_args("hevc_amf", 24),
+            ["-rc", "cqp", "-qp_i", "24", "-qp_p", "24"]
+        );
+
+        assert_eq!(speed_args("h264_nvenc", SpeedPreset::Best), ["-preset", "p7"]);
+        assert_eq!(speed_args("hevc_qsv", SpeedPreset::Balanced), ["-preset", "medium"]);
+        assert_eq!(speed_args("h264_vaapi", SpeedPreset::Balanced), ["-compression_level", "4"]);
+        assert_eq!(speed_args("h264_amf", SpeedPreset::Balanced), ["-quality", "balanced"]);
+        assert_eq!(speed_args("libx265", SpeedPreset::Best), ["-preset", "veryslow"]);
+
+        assert_eq!(bitrate_mode_args("h264_nvenc", BitrateMode::Constant), ["-rc", "cbr"]);
+        assert_eq!(bitrate_mode_args("hevc_vaapi", BitrateMode::Variable), ["-rc_mode", "VBR"]);
+        assert!(bitrate_mode_args("libx264", BitrateMode::Constant).is_empty());
+    }
+
+    #[test]
+    fn test_av1_vp9_mappings() {
+        assert_eq!(quality_args("libsvtav1", 23), ["-crf", "28"]);
+        assert_eq!(quality_args("libx264", 0), ["-crf", "0"]);
+        assert_eq!(quality_args("hevc_vaapi", 0), ["-rc_mode", "CQP", "-qp", "1"]);
+        assert_eq!(quality_args("libaom-av1", 51), ["-crf", "63", "-b:v", "0"]);
+        assert_eq!(quality_args("libvpx-vp9", 1), ["-crf", "1", "-b:v", "0"]);
+        assert_eq!(quality_args("librav1e", 23), ["-qp", "115"]);
+
+        assert_eq!(speed_args("libsvtav1", SpeedPreset::Balanced), ["-preset", "8"]);
+        assert_eq!(speed_args("librav1e", SpeedPreset::Fastest), ["-speed", "10"]);
+        assert_eq!(
+            speed_args("libaom-av1", SpeedPreset::Best),
+            ["-cpu-used", "1", "-row-mt", "1"]
+        );
+        assert_eq!(
+            speed_args("libvpx-vp9", SpeedPreset::Quality),
+            ["-cpu-used", "1", "-deadline", "good", "-row-mt", "1"]
+        );
+
+        assert!(!EncoderFamily::of("libsvtav1").supports_two_pass());
+        assert!(EncoderFamily::of("libvpx-vp9").supports_two_pass());
+        assert!(!EncoderFamily::of("libaom-av1").is_hardware());
+    }
+
+    #[test]
+    fn test_hw_init_failure_detection() {
+        let tail = |lines: &[&str]| lines.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+
+        let nvenc = tail(&[
+            "[h264_nvenc @ 0x55] OpenEncodeSessionEx failed: out of memory (10): (no details)",
+            "[vost#0:0/h264_nvenc @ 0x56] Error while opening encoder - maybe incorrect parameters",
+        ]);
+        assert_eq!(hw_init_failure(&nvenc).as_deref(), Some(nvenc[0].as_str()));
+
+        let vaapi = tail(&["[AVHWDeviceContext @ 0x1] Failed to initialise VAAPI connection: -1 (unknown libva error)."]);
+        assert!(hw_init_failure(&vaapi).is_some());
+
+        // 输入文件损坏、编码参数错误等与硬件初始化无关的错误不触发回退
+        let other = tail(&["in.mkv: Invalid data found when processing input"]);
+        assert_eq!(hw_init_failure(&other), None);
+        let bad_profile = tail(&[
+            "[h264_nvenc @ 0x55] Unsupported profile: high444",
+            "[vost#0:0/h264_nvenc @ 0x56] Error while opening encoder - maybe incorrect parameters",
+        ]);
+        assert_eq!(hw_init_failure(&bad_profile), None);
+    }
+}
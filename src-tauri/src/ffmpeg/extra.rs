@@ -0,0 +1,221 @@
+//! 用户自定义的额外 FFmpeg 参数
+//!
+//! 按 shell 规则拆分后插入两个固定位置：输入选项紧接在 `-i` 之前，输出选项在输出路径之前。
+//! 会与构建器生成的参数冲突（输入输出、滤镜图、流映射、剪辑等）或会读写任意文件的选项被拒绝；
+//! 每个选项必须带一个值（已知的开关选项除外），游离的参数会被 FFmpeg 当作额外的输出文件，同样拒绝。
+
+use crate::types::ExtraArgs;
+
+/// 由构建器控制的选项
+const CONFLICTING: &[&str] = &[
+    "y",
+    "n",
+    "i",
+    "f",
+    "filter_complex",
+    "lavfi",
+    "vf",
+    "af",
+    "filter",
+    "map",
+    "vcodec",
+    "pass",
+    "ss",
+    "sseof",
+    "t",
+    "to",
+    "x264-params",
+    "x264opts",
+    "x265-params",
+    "svtav1-params",
+    "aom-params",
+    "rav1e-params",
+];
+
+/// 会读写任意文件的选项
+const FILE_ACCESS: &[&str] = &[
+    "passlogfile",
+    "filter_script",
+    "filter_complex_script",
+    "vstats",
+    "vstats_file",
+    "report",
+    "progress",
+    "dump_attachment",
+    "attach",
+    "sdp_file",
+    "fpre",
+    "stats_enc_pre",
+    "stats_enc_post",
+    "stats_mux_pre",
+];
+
+/// 不带值的开关选项
+const FLAGS: &[&str] = &[
+    "an",
+    "vn",
+    "sn",
+    "dn",
+    "re",
+    "shortest",
+    "copyts",
+    "start_at_zero",
+    "accurate_seek",
+    "noaccurate_seek",
+    "ignore_unknown",
+    "copy_unknown",
+    "hide_banner",
+    "nostdin",
+    "stats",
+    "nostats",
+    "benchmark",
+    "xerror",
+];
+
+/// 按 shell 规则拆分参数：空白分隔，支持单引号、双引号和反斜杠转义
+pub fn split(text: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            '\'' => {
+                in_arg = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => current.push(c),
+                        None => return Err("额外参数中的单引号未闭合".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                in_arg = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        // 双引号内只有 `"` `\` `$` `` ` `` 可以转义
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => current.push(c),
+                            Some(c) => {
+                                current.push('\\');
+                                current.push(c);
+                            }
+                            None => return Err("额外参数中的双引号未闭合".to_string()),
+                        },
+                        Some(c) => current.push(c),
+                        None => return Err("额外参数中的双引号未闭合".to_string()),
+                    }
+                }
+            }
+            '\\' => {
+                in_arg = true;
+                match chars.next() {
+                    Some(c) => current.push(c),
+                    None => return Err("额外参数以反斜杠结尾".to_string()),
+                }
+            }
+            c => {
+                in_arg = true;
+                current.push(c);
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+/// 拆分并检查一组额外参数
+fn parse(text: &str) -> Result<Vec<String>, String> {
+    let args = split(text)?;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let Some(option) = arg.strip_prefix('-').filter(|o| !o.is_empty()) else {
+            return Err(format!(
+                "多余的参数 {arg}：额外参数只能是选项，不能指定输出文件"
+            ));
+        };
+        // FFmpeg 7 的 `-/option file` 从文件读取选项值
+        if option.starts_with('/') {
+            return Err(format!("额外参数 {arg} 会读取文件，不允许使用"));
+        }
+        // 去掉流说明符，如 `-b:v` → `b`
+        let (name, specifier) = option.split_once(':').unwrap_or((option, ""));
+        // 只允许明确指向音频或字幕流的编码器选项，`-c:V`、`-c:0` 等都可能选中视频流
+        let video_codec = matches!(name, "c" | "codec") && !specifier.starts_with(['a', 's']);
+        if CONFLICTING.contains(&name) || video_codec {
+            return Err(format!("额外参数 {arg} 与程序生成的参数冲突"));
+        }
+        if FILE_ACCESS.contains(&name) {
+            return Err(format!("额外参数 {arg} 会读写任意文件，不允许使用"));
+        }
+        if !FLAGS.contains(&name) && iter.next().is_none() {
+            return Err(format!("额外参数 {arg} 缺少值"));
+        }
+    }
+    Ok(args)
+}
+
+/// 检查额外参数
+pub fn validate(extra: &ExtraArgs) -> Result<(), String> {
+    input_args(extra)?;
+    output_args(extra)?;
+    Ok(())
+}
+
+/// 插入到 `-i` 之前的输入选项
+pub fn input_args(extra: &ExtraArgs) -> Result<Vec<String>, String> {
+    parse(&extra.input).map_err(|e| format!("输入选项：{e}"))
+}
+
+/// 插入到输出路径之前的输出选项
+pub fn output_args(extra: &ExtraArgs) -> Result<Vec<String>, String> {
+    parse(&extra.output).map_err(|e| format!("输出选项：{e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        assert_eq!(
+            split(r#"-metadata title="My \"Show\"" -metadata:s:a:0 'language=jpn'  a\ b"#).unwrap(),
+            [
+                "-metadata",
+                "title=My \"Show\"",
+                "-metadata:s:a:0",
+                "language=jpn",
+                "a b"
+            ]
+        );
+        assert_eq!(split("  ").unwrap(), Vec::<String>::new());
+        assert!(split("-metadata 'title").is_err());
+    }
+
+    #[test]
+    fn test_reject_conflicts() {
+        assert!(parse("-tag:v hvc1 -shortest -b:a:1 96k").is_ok());
+        assert!(parse("-c:s mov_text").is_ok());
+        assert!(parse("-y").is_err());
+        assert!(parse("-c:v libx265").is_err());
+        assert!(parse("-c:V libx265").is_err());
+        assert!(parse("-codec:0 libx265").is_err());
+        assert!(parse("-x264opts stats=/tmp/x").is_err());
+        assert!(parse("-/filter:v /etc/passwd").is_err());
+        assert!(parse("-filter_complex '[0:v]null'").is_err());
+        assert!(parse("-vstats_file /etc/passwd").is_err());
+        assert!(parse("-tag:v hvc1 /tmp/extra.mp4").is_err());
+        assert!(parse("-tag:v").is_err());
+    }
+}
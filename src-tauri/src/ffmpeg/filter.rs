@@ -0,0 +1,351 @@
+//! 类型化的 FFmpeg 滤镜图构建器
+//!
+//! FFmpeg 对滤镜描述做两级解析：先由滤镜图解析器按 `[ ] , ;` 切分滤镜和连接，
+//! 再由各滤镜按 `key=value:key=value` 解析选项。因此选项值需要依次做两级转义：
+//!
+//! 1. 选项值级：转义 `\` `'` `:` `=`（`=` 会让无名选项被误认为键值对）
+//! 2. 滤镜图级：转义 `\` `'` `[` `]` `,` `;`
+//!
+//! 参见 FFmpeg 文档 “Notes on filtergraph escaping”。
+
+use std::fmt;
+
+/// 滤镜选项值
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionValue {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl fmt::Display for OptionValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Str(s) => f.write_str(s),
+            Self::Int(i) => write!(f, "{i}"),
+            Self::Float(v) => write!(f, "{v}"),
+            Self::Bool(b) => f.write_str(if *b { "1" } else { "0" }),
+        }
+    }
+}
+
+impl From<&str> for OptionValue {
+    fn from(s: &str) -> Self {
+        Self::Str(s.to_string())
+    }
+}
+
+impl From<String> for OptionValue {
+    fn from(s: String) -> Self {
+        Self::Str(s)
+    }
+}
+
+impl From<i64> for OptionValue {
+    fn from(v: i64) -> Self {
+        Self::Int(v)
+    }
+}
+
+impl From<i32> for OptionValue {
+    fn from(v: i32) -> Self {
+        Self::Int(v.into())
+    }
+}
+
+impl From<u32> for OptionValue {
+    fn from(v: u32) -> Self {
+        Self::Int(v.into())
+    }
+}
+
+impl From<f64> for OptionValue {
+    fn from(v: f64) -> Self {
+        Self::Float(v)
+    }
+}
+
+impl From<bool> for OptionValue {
+    fn from(v: bool) -> Self {
+        Self::Bool(v)
+    }
+}
+
+/// 单个滤镜节点，如 `subtitles=filename=a.ass:charenc=gbk`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    name: String,
+    options: Vec<(Option<String>, OptionValue)>,
+}
+
+impl Filter {
+    pub fn new(name: &str) -> Self {
+        debug_assert!(is_identifier(name), "非法滤镜名: {name}");
+        Self {
+            name: name.to_string(),
+            options: Vec::new(),
+        }
+    }
+
+    /// 添加按位置传递的无名选项
+    pub fn arg(mut self, value: impl Into<OptionValue>) -> Self {
+        self.options.push((None, value.into()));
+        self
+    }
+
+    /// 添加 `key=value` 形式的命名选项
+    pub fn opt(mut self, key: &str, value: impl Into<OptionValue>) -> Self {
+        debug_assert!(is_identifier(key), "非法选项名: {key}");
+        self.options.push((Some(key.to_string()), value.into()));
+        self
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)?;
+        for (i, (key, value)) in self.options.iter().enumerate() {
+            f.write_str(if i == 0 { "=" } else { ":" })?;
+            if let Some(key) = key {
+                write!(f, "{key}=")?;
+            }
+            f.write_str(&escape_graph(&escape_option_value(&value.to_string())))?;
+        }
+        Ok(())
+    }
+}
+
+/// 由逗号连接的滤镜链，可带输入/输出连接标签
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FilterChain {
+    inputs: Vec<String>,
+    filters: Vec<Filter>,
+    outputs: Vec<String>,
+}
+
+impl FilterChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 添加输入连接标签，如 `0:v` 或上一条链的输出标签
+    pub fn input(mut self, label: &str) -> Self {
+        debug_assert!(is_pad_label(label), "非法连接标签: {label}");
+        self.inputs.push(label.to_string());
+        self
+    }
+
+    /// 添加输出连接标签
+    pub fn output(mut self, label: &str) -> Self {
+        debug_assert!(is_pad_label(label), "非法连接标签: {label}");
+        self.outputs.push(label.to_string());
+        self
+    }
+
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    pub fn push(&mut self, filter: Filter) {
+        self.filters.push(filter);
+    }
+
+    /// 把另一条链的滤镜接在末尾（忽略其连接标签）
+    pub fn append(&mut self, other: FilterChain) {
+        self.filters.extend(other.filters);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+}
+
+impl fmt::Display for FilterChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for label in &self.inputs {
+            write!(f, "[{label}]")?;
+        }
+        for (i, filter) in self.filters.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{filter}")?;
+        }
+        for label in &self.outputs {
+            write!(f, "[{label}]")?;
+        }
+        Ok(())
+    }
+}
+
+/// 由分号连接的多条滤镜链，用于 `-filter_complex`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FilterGraph {
+    chains: Vec<FilterChain>,
+}
+
+impl FilterGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, chain: FilterChain) {
+        self.chains.push(chain);
+    }
+}
+
+impl fmt::Display for FilterGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, chain) in self.chains.iter().filter(|c| !c.is_empty()).enumerate() {
+            if i > 0 {
+                f.write_str(";")?;
+            }
+            write!(f, "{chain}")?;
+        }
+        Ok(())
+    }
+}
+
+/// 第一级转义：滤镜选项值
+fn escape_option_value(value: &str) -> String {
+    escape_chars(value, &['\\', '\'', ':', '='])
+}
+
+/// 第二级转义：滤镜图描述
+fn escape_graph(desc: &str) -> String {
+    escape_chars(desc, &['\\', '\'', '[', ']', ',', ';'])
+}
+
+fn escape_chars(s: &str, special: &[char]) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if special.contains(&c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn is_identifier(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn is_pad_label(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | ':'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 按 FFmpeg `av_get_token` 的规则反转义一级，用于校验往返一致性
+    fn unescape_level(s: &str) -> String {
+        let mut out = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                if let Some(next) = chars.next() {
+                    out.push(next);
+                }
+            } else {
+                out.push(c);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_option_value_escaping() {
+        assert_eq!(escape_option_value("a:b"), "a\\:b");
+        assert_eq!(escape_option_value("a=b"), "a\\=b");
+        assert_eq!(escape_option_value("it's"), "it\\'s");
+        assert_eq!(escape_option_value("C:\\sub"), "C\\:\\\\sub");
+        // 逗号、方括号和百分号在选项值级不是特殊字符
+        assert_eq!(escape_option_value("a,b[c]%d"), "a,b[c]%d");
+    }
+
+    #[test]
+    fn test_graph_escaping() {
+        assert_eq!(escape_graph("a,b;c"), "a\\,b\\;c");
+        assert_eq!(escape_graph("[x]"), "\\[x\\]");
+        assert_eq!(escape_graph("\\'"), "\\\\\\'");
+        assert_eq!(escape_graph("a:b=c%"), "a:b=c%");
+    }
+
+    #[test]
+    fn test_ffmpeg_documentation_example() {
+        let filter = Filter::new("drawtext")
+            .opt("text", "this is a 'string': may contain one, or more, special characters");
+        assert_eq!(
+            filter.to_string(),
+            "drawtext=text=this is a \\\\\\'string\\\\\\'\\\\: may contain one\\, or more\\, special characters"
+        );
+    }
+
+    #[test]
+    fn test_round_trip_of_tricky_values() {
+        let values = [
+            "C:\\Users\\me\\字幕 [v2], final; it's.ass",
+            "/mnt/a=b/100%/x:y.srt",
+            "\\\\server\\share\\a'b'c.ass",
+            "",
+        ];
+        for value in values {
+            let rendered = Filter::new("subtitles").arg(value).to_string();
+            let desc = rendered.strip_prefix("subtitles=").unwrap();
+            assert_eq!(unescape_level(&unescape_level(desc)), value);
+        }
+    }
+
+    #[test]
+    fn test_filter_options() {
+        assert_eq!(Filter::new("null").to_string(), "null");
+        assert_eq!(
+            Filter::new("scale").arg(1280).arg(-2).opt("flags", "lanczos").to_string(),
+            "scale=1280:-2:flags=lanczos"
+        );
+        assert_eq!(
+            Filter::new("fps").opt("fps", 23.976).to_string(),
+            "fps=fps=23.976"
+        );
+        assert_eq!(
+            Filter::new("subtitles").opt("alpha", true).to_string(),
+            "subtitles=alpha=1"
+        );
+        assert_eq!(
+            Filter::new("subtitles")
+                .opt("force_style", "PrimaryColour=&H00FFFFFF,Outline=2")
+                .to_string(),
+            "subtitles=force_style=PrimaryColour\\\\=&H00FFFFFF\\,Outline\\\\=2"
+        );
+    }
+
+    #[test]
+    fn test_chain_and_graph() {
+        let chain = FilterChain::new()
+            .filter(Filter::new("setpts").arg("PTS-STARTPTS"))
+            .filter(Filter::new("format").arg("nv12"));
+        assert_eq!(chain.to_string(), "setpts=PTS-STARTPTS,format=nv12");
+
+        let mut graph = FilterGraph::new();
+        graph.push(
+            FilterChain::new()
+                .input("0:v")
+                .filter(Filter::new("split"))
+                .output("a")
+                .output("b"),
+        );
+        graph.push(FilterChain::new());
+        graph.push(
+            FilterChain::new()
+                .input("a")
+                .input("b")
+                .filter(Filter::new("hstack"))
+                .output("out"),
+        );
+        assert_eq!(graph.to_string(), "[0:v]split[a][b];[a][b]hstack[out]");
+    }
+}
@@ -0,0 +1,215 @@
+//! EBU R128 响度标准化（`loudnorm` 两遍模式）
+//!
+//! 第一遍对每条输出音轨运行 `loudnorm=print_format=json` 测量，从 stderr 末尾解析统计值；
+//! 第二遍把测量值传回 `loudnorm` 做线性增益调整。未测量时（如预览片段）使用单遍动态模式。
+
+use crate::ffmpeg::audio;
+use crate::ffmpeg::filter::{Filter, FilterChain};
+use crate::ffmpeg::trim;
+use crate::types::{EncodeParams, LoudnessStats, LoudnormTarget};
+
+/// 未知源采样率时的输出采样率；`loudnorm` 内部会把音频上采样到 192 kHz，需要重新采样
+const FALLBACK_SAMPLE_RATE: u32 = 48000;
+
+/// 测量每条输出音轨响度的 FFmpeg 参数，每条音轨一遍
+///
+/// 未指定音轨时只测量 FFmpeg 默认选择的那条音轨，与正式编码的选择一致；
+/// 剪辑时只测量保留的时间段：单段使用输入端 seek，多段用 `aselect` 挑选音频帧。
+pub fn measure_args(params: &EncodeParams, target: &LoudnormTarget) -> Vec<Vec<String>> {
+    let maps: Vec<Option<String>> = if params.audio_tracks.is_empty() {
+        vec![None]
+    } else {
+        audio::stream_specifiers(&params.audio_tracks)
+            .into_iter()
+            .map(Some)
+            .collect()
+    };
+
+    let mut chain = FilterChain::new();
+    if params.trim.len() > 1 {
+        chain.push(Filter::new("aselect").arg(trim::select_expr(&params.trim)));
+        chain.push(Filter::new("asetpts").arg("N/SR/TB"));
+    }
+    chain.push(
+        Filter::new("loudnorm")
+            .opt("I", target.integrated)
+            .opt("TP", target.true_peak)
+            .opt("LRA", target.lra)
+            .opt("print_format", "json"),
+    );
+
+    maps.into_iter()
+        .map(|map| {
+            let mut args = match trim::single_range(&params.trim) {
+                Some(range) => trim::input_seek_args(range.start, range.end.map(|e| e - range.start)),
+                None => Vec::new(),
+            };
+            args.push("-i".to_string());
+            args.push(params.video_path.clone());
+            if let Some(map) = map {
+                args.push("-map".to_string());
+                args.push(map);
+            }
+            args.extend(["-vn", "-sn", "-dn", "-af"].map(String::from));
+            args.push(chain.to_string());
+            args.extend(["-f", "null", "-y", "-"].map(String::from));
+            args
+        })
+        .collect()
+}
+
+/// 从测量遍的 stderr 中解析 `loudnorm` 输出的 JSON 统计块（位于输出末尾）
+pub fn parse_stats(log: &[String]) -> Result<LoudnessStats, String> {
+    let text = log.join("\n");
+    let start = text
+        .rfind('{')
+        .ok_or_else(|| "未找到 loudnorm 测量结果".to_string())?;
+    let end = text[start..]
+        .find('}')
+        .map(|i| start + i + 1)
+        .ok_or_else(|| "loudnorm 测量结果不完整".to_string())?;
+    let json: serde_json::Value = serde_json::from_str(&text[start..end])
+        .map_err(|e| format!("解析 loudnorm 测量结果失败: {e}"))?;
+
+    // 各值以字符串输出，静音时为 `-inf`
+    let field = |key: &str| -> Result<f64, String> {
+        json[key]
+            .as_str()
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .ok_or_else(|| format!("loudnorm 测量结果缺少 {key}"))
+    };
+
+    Ok(LoudnessStats {
+        input_i: field("input_i")?,
+        input_tp: field("input_tp")?,
+        input_lra: field("input_lra")?,
+        input_thresh: field("input_thresh")?,
+        target_offset: field("target_offset")?,
+        sample_rate: None,
+    })
+}
+
+/// 测量值是否可用于线性调整；静音音轨的响度为负无穷
+pub fn is_measurable(stats: &LoudnessStats) -> bool {
+    [stats.input_i, stats.input_tp, stats.input_lra, stats.input_thresh, stats.target_offset]
+        .iter()
+        .all(|v| v.is_finite())
+}
+
+/// 单条音轨的标准化滤镜链；`stats` 为空时使用动态模式
+pub fn filter_chain(target: &LoudnormTarget, stats: Option<&LoudnessStats>) -> FilterChain {
+    let mut loudnorm = Filter::new("loudnorm")
+        .opt("I", target.integrated)
+        .opt("TP", target.true_peak)
+        .opt("LRA", target.lra);
+    if let Some(stats) = stats {
+        loudnorm = loudnorm
+            .opt("measured_I", stats.input_i)
+            .opt("measured_TP", stats.input_tp)
+            .opt("measured_LRA", stats.input_lra)
+            .opt("measured_thresh", stats.input_thresh)
+            .opt("offset", stats.target_offset)
+            .opt("linear", true);
+    }
+
+    let sample_rate = stats
+        .and_then(|s| s.sample_rate)
+        .unwrap_or(FALLBACK_SAMPLE_RATE);
+    FilterChain::new()
+        .filter(loudnorm)
+        .filter(Filter::new("aresample").arg(sample_rate))
+}
+
+/// 各输出音轨的标准化滤镜链，顺序与输出音轨一致；测量结果为静音的音轨为空链
+///
+/// 未启用标准化时返回空列表。
+pub fn track_chains(params: &EncodeParams) -> Vec<FilterChain> {
+    let Some(target) = &params.loudnorm else {
+        return Vec::new();
+    };
+
+    (0..params.audio_tracks.len().max(1))
+        .map(|i| match params.loudness.get(i) {
+            Some(stats) if !is_measurable(stats) => FilterChain::new(),
+            // 尚未测量（如预览片段）时使用动态模式
+            stats => filter_chain(target, stats),
+        })
+        .collect()
+}
+
+/// 正式编码中各音轨的 `-filter:a` 参数；未启用标准化时返回空列表
+pub fn filter_args(params: &EncodeParams) -> Vec<String> {
+    let mut args = Vec::new();
+    for (i, chain) in track_chains(params).into_iter().enumerate() {
+        if chain.is_empty() {
+            continue;
+        }
+        args.push(if params.audio_tracks.is_empty() {
+            "-filter:a".to_string()
+        } else {
+            format!("-filter:a:{i}")
+        });
+        args.push(chain.to_string());
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET: LoudnormTarget = LoudnormTarget {
+        integrated: -23.0,
+        true_peak: -1.0,
+        lra: 7.0,
+    };
+
+    fn measurement_log() -> Vec<String> {
+        [
+            "size=N/A time=00:23:59.98 bitrate=N/A speed= 412x",
+            "[Parsed_loudnorm_0 @ 0x55d0c8e0f0c0] ",
+            "{",
+            "\t\"input_i\" : \"-27.61\",",
+            "\t\"input_tp\" : \"-4.47\",",
+            "\t\"input_lra\" : \"18.06\",",
+            "\t\"input_thresh\" : \"-39.20\",",
+            "\t\"output_i\" : \"-23.58\",",
+            "\t\"normalization_type\" : \"dynamic\",",
+            "\t\"target_offset\" : \"0.58\"",
+            "}",
+        ]
+        .map(String::from)
+        .to_vec()
+    }
+
+    #[test]
+    fn test_parse_stats() {
+        let stats = parse_stats(&measurement_log()).unwrap();
+        assert_eq!(stats.input_i, -27.61);
+        assert_eq!(stats.input_thresh, -39.2);
+        assert_eq!(stats.target_offset, 0.58);
+        assert!(is_measurable(&stats));
+
+        let silent: Vec<String> = measurement_log()
+            .into_iter()
+            .map(|l| l.replace("-27.61", "-inf"))
+            .collect();
+        assert!(!is_measurable(&parse_stats(&silent).unwrap()));
+        assert!(parse_stats(&["Error opening input".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_filter_chain() {
+        let mut stats = parse_stats(&measurement_log()).unwrap();
+        stats.sample_rate = Some(44100);
+        assert_eq!(
+            filter_chain(&TARGET, Some(&stats)).to_string(),
+            "loudnorm=I=-23:TP=-1:LRA=7:measured_I=-27.61:measured_TP=-4.47:measured_LRA=18.06:\
+             measured_thresh=-39.2:offset=0.58:linear=1,aresample=44100"
+        );
+        assert_eq!(
+            filter_chain(&TARGET, None).to_string(),
+            "loudnorm=I=-23:TP=-1:LRA=7,aresample=48000"
+        );
+    }
+}
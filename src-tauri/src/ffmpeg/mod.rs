@@ -1,3 +1,18 @@
 pub mod args;
+pub mod audio;
+pub mod capability;
+pub mod command;
+pub mod compat;
+pub mod encoder;
+pub mod extra;
+pub mod filter;
+pub mod loudnorm;
+pub mod probe;
+pub mod probe_cache;
 pub mod progress;
 pub mod runner;
+pub mod subtitle;
+pub mod template;
+pub mod trim;
+pub mod video;
+pub mod x26x;
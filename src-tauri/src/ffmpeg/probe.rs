@@ -0,0 +1,238 @@
+//! 解析 ffprobe 的 JSON 输出为 [`MediaInfo`]
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::types::{
+    Attachment, AudioStream, Chapter, HdrTransfer, MediaInfo, SubtitleStream, VideoStream,
+};
+
+/// 解析 `ffprobe -show_format -show_streams -show_chapters -of json` 的输出
+pub fn parse_media_info(json: &Value) -> MediaInfo {
+    let format = &json["format"];
+    let mut info = MediaInfo {
+        format_name: format["format_name"].as_str().unwrap_or("unknown").to_string(),
+        duration: number(&format["duration"]),
+        size: number(&format["size"]).map(|v| v as u64),
+        bit_rate: number(&format["bit_rate"]).map(|v| v as u64),
+        tags: tags(&format["tags"]),
+        ..MediaInfo::default()
+    };
+
+    for stream in json["streams"].as_array().into_iter().flatten() {
+        let index = stream["index"].as_u64().unwrap_or(0) as u32;
+        let codec = text(&stream["codec_name"]).unwrap_or_else(|| "unknown".to_string());
+        match stream["codec_type"].as_str() {
+            Some("video") => info.video.push(VideoStream {
+                index,
+                codec,
+                profile: text(&stream["profile"]),
+                pix_fmt: text(&stream["pix_fmt"]),
+                width: stream["width"].as_u64().unwrap_or(0) as u32,
+                height: stream["height"].as_u64().unwrap_or(0) as u32,
+                frame_rate: frame_rate(stream),
+                bit_depth: bit_depth(stream),
+                color_primaries: text(&stream["color_primaries"]),
+                color_transfer: text(&stream["color_transfer"]),
+                color_space: text(&stream["color_space"]),
+                color_range: text(&stream["color_range"]),
+                hdr: hdr_transfer(stream["color_transfer"].as_str()),
+                rotation: rotation(stream),
+                sample_aspect_ratio: text(&stream["sample_aspect_ratio"]),
+                display_aspect_ratio: text(&stream["display_aspect_ratio"]),
+                attached_pic: flag(stream, "attached_pic"),
+            }),
+            Some("audio") => info.audio.push(AudioStream {
+                index,
+                codec,
+                profile: text(&stream["profile"]),
+                channels: stream["channels"].as_u64().unwrap_or(0) as u32,
+                channel_layout: text(&stream["channel_layout"]),
+                sample_rate: number(&stream["sample_rate"]).map(|v| v as u32),
+                bit_rate: [&stream["bit_rate"], &stream["tags"]["BPS"], &stream["tags"]["BPS-eng"]]
+                    .into_iter()
+                    .find_map(number)
+                    .map(|v| v as u64),
+                language: language(stream),
+                title: text(&stream["tags"]["title"]),
+                default: flag(stream, "default"),
+            }),
+            Some("subtitle") => info.subtitles.push(SubtitleStream {
+                index,
+                codec,
+                language: language(stream),
+                title: text(&stream["tags"]["title"]),
+                default: flag(stream, "default"),
+                forced: flag(stream, "forced"),
+            }),
+            Some("attachment") => info.attachments.push(Attachment {
+                index,
+                filename: text(&stream["tags"]["filename"]),
+                mimetype: text(&stream["tags"]["mimetype"]),
+            }),
+            _ => {}
+        }
+    }
+
+    info.chapters = json["chapters"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|c| Chapter {
+            start: number(&c["start_time"]).unwrap_or(0.0),
+            end: number(&c["end_time"]).unwrap_or(0.0),
+            title: text(&c["tags"]["title"]),
+        })
+        .collect();
+
+    info
+}
+
+/// 非空字符串字段
+fn text(value: &Value) -> Option<String> {
+    value.as_str().filter(|s| !s.is_empty()).map(str::to_string)
+}
+
+/// ffprobe 的数值字段大多以字符串输出，两种形式都接受
+fn number(value: &Value) -> Option<f64> {
+    value
+        .as_f64()
+        .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+}
+
+fn flag(stream: &Value, key: &str) -> bool {
+    stream["disposition"][key].as_i64().unwrap_or(0) != 0
+}
+
+/// 语言标签，忽略表示未定义的 `und`
+fn language(stream: &Value) -> Option<String> {
+    text(&stream["tags"]["language"]).filter(|l| l != "und")
+}
+
+fn tags(value: &Value) -> BTreeMap<String, String> {
+    value
+        .as_object()
+        .map(|map| {
+            map.iter()
+                .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 解析 `24000/1001` 形式的帧率，平均帧率无效（`0/0`）时回退到 `r_frame_rate`
+fn frame_rate(stream: &Value) -> Option<f64> {
+    ["avg_frame_rate", "r_frame_rate"].iter().find_map(|key| {
+        let (num, den) = stream[*key].as_str()?.split_once('/')?;
+        let (num, den) = (num.parse::<f64>().ok()?, den.parse::<f64>().ok()?);
+        (num > 0.0 && den > 0.0).then(|| num / den)
+    })
+}
+
+/// 位深优先取 `bits_per_raw_sample`，缺失时从像素格式推断（如 `yuv420p10le`、`p010le`）
+fn bit_depth(stream: &Value) -> Option<u32> {
+    if let Some(bits) = number(&stream["bits_per_raw_sample"]).filter(|b| *b > 0.0) {
+        return Some(bits as u32);
+    }
+    let pix_fmt = stream["pix_fmt"].as_str()?;
+    let depth = if pix_fmt.starts_with("p010") || pix_fmt.contains("10le") || pix_fmt.contains("10be") {
+        10
+    } else if pix_fmt.contains("12le") || pix_fmt.contains("12be") {
+        12
+    } else {
+        8
+    };
+    Some(depth)
+}
+
+/// 由传输特性判断 HDR 格式
+fn hdr_transfer(transfer: Option<&str>) -> Option<HdrTransfer> {
+    match transfer? {
+        "smpte2084" => Some(HdrTransfer::Pq),
+        "arib-std-b67" => Some(HdrTransfer::Hlg),
+        _ => None,
+    }
+}
+
+/// 旋转角度：优先读取显示矩阵 side data，旧版 FFmpeg 只有顺时针的 `rotate` 标签
+fn rotation(stream: &Value) -> i32 {
+    let degrees = stream["side_data_list"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find_map(|d| number(&d["rotation"]))
+        .or_else(|| number(&stream["tags"]["rotate"]).map(|r| -r))
+        .unwrap_or(0.0)
+        .round() as i32;
+    // 归一化到 (-180, 180]
+    let normalized = degrees.rem_euclid(360);
+    if normalized > 180 { normalized - 360 } else { normalized }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_media_info() {
+        let json: Value = serde_json::from_str(
+            r#"{
+                "streams": [
+                    {"index": 0, "codec_name": "hevc", "codec_type": "video", "profile": "Main 10",
+                     "width": 3840, "height": 2160, "pix_fmt": "yuv420p10le",
+                     "sample_aspect_ratio": "1:1", "display_aspect_ratio": "16:9",
+                     "avg_frame_rate": "24000/1001", "color_transfer": "smpte2084",
+                     "color_primaries": "bt2020", "side_data_list": [{"rotation": -90}],
+                     "disposition": {"default": 1, "attached_pic": 0}},
+                    {"index": 1, "codec_name": "truehd", "codec_type": "audio", "channels": 8,
+                     "channel_layout": "7.1", "sample_rate": "48000",
+                     "disposition": {"default": 1}, "tags": {"language": "jpn", "BPS": "3500000"}},
+                    {"index": 2, "codec_name": "hdmv_pgs_subtitle", "codec_type": "subtitle",
+                     "disposition": {"default": 0, "forced": 1}, "tags": {"language": "und"}},
+                    {"index": 3, "codec_name": "ttf", "codec_type": "attachment",
+                     "tags": {"filename": "font.ttf", "mimetype": "font/ttf"}}
+                ],
+                "chapters": [{"start_time": "0.000000", "end_time": "90.500000", "tags": {"title": "OP"}}],
+                "format": {"format_name": "matroska,webm", "duration": "1440.5", "size": "1000",
+                           "tags": {"title": "Ep 1"}}
+            }"#,
+        )
+        .unwrap();
+        let info = parse_media_info(&json);
+
+        assert_eq!(info.format_name, "matroska,webm");
+        assert_eq!(info.duration, Some(1440.5));
+        assert_eq!(info.size, Some(1000));
+        assert_eq!(info.tags["title"], "Ep 1");
+
+        let video = &info.video[0];
+        assert_eq!((video.width, video.height), (3840, 2160));
+        assert_eq!(video.bit_depth, Some(10));
+        assert_eq!(video.rotation, -90);
+        assert!((video.frame_rate.unwrap() - 23.976).abs() < 0.001);
+        assert_eq!(video.color_transfer.as_deref(), Some("smpte2084"));
+        assert_eq!(video.hdr, Some(HdrTransfer::Pq));
+
+        let audio = &info.audio[0];
+        assert_eq!(audio.index, 1);
+        assert_eq!(audio.language.as_deref(), Some("jpn"));
+        assert_eq!(audio.sample_rate, Some(48000));
+        assert_eq!(audio.bit_rate, Some(3_500_000));
+        assert!(audio.default);
+
+        assert!(info.subtitles[0].forced);
+        assert_eq!(info.subtitles[0].language, None);
+        assert_eq!(info.attachments[0].filename.as_deref(), Some("font.ttf"));
+        assert_eq!(info.chapters[0].title.as_deref(), Some("OP"));
+        assert_eq!(info.chapters[0].end, 90.5);
+    }
+
+    #[test]
+    fn test_legacy_rotate_tag() {
+        let stream: Value = serde_json::from_str(r#"{"tags": {"rotate": "270"}}"#).unwrap();
+        assert_eq!(rotation(&stream), 90);
+        let stream: Value = serde_json::from_str(r#"{"pix_fmt": "p010le"}"#).unwrap();
+        assert_eq!(bit_depth(&stream), Some(10));
+    }
+}
@@ -0,0 +1,186 @@
+//! ffprobe 结果缓存
+//!
+//! 以规范化路径、文件大小和修改时间为键缓存 [`MediaInfo`]，文件变化后自动失效；
+//! 缓存保存在磁盘上，避免每次启动后重新探测网络挂载盘上的大文件。
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::MediaInfo;
+
+/// 缓存条目上限，超出时淘汰最早探测的条目
+const MAX_ENTRIES: usize = 500;
+
+/// 进程内缓存，首次使用时从磁盘加载
+static CACHE: Mutex<Option<ProbeCache>> = Mutex::new(None);
+
+/// 文件的身份信息：规范化路径、大小和修改时间（Unix 毫秒）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint {
+    pub path: String,
+    pub size: u64,
+    pub modified: u64,
+}
+
+impl Fingerprint {
+    /// 读取文件元数据，文件不存在或无法访问时返回 `None`
+    pub fn of(path: &str) -> Option<Self> {
+        let canonical = Path::new(path).canonicalize().ok()?;
+        let meta = std::fs::metadata(&canonical).ok()?;
+        let modified = meta
+            .modified()
+            .ok()?
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_millis() as u64;
+        Some(Self {
+            path: canonical.to_string_lossy().to_string(),
+            size: meta.len(),
+            modified,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    size: u64,
+    modified: u64,
+    /// 写入缓存的时间（Unix 秒），用于淘汰
+    probed_at: u64,
+    info: MediaInfo,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProbeCache {
+    entries: HashMap<String, CacheEntry>,
+}
+
+impl ProbeCache {
+    /// 查找缓存，大小或修改时间不一致时视为失效
+    pub fn get(&self, fp: &Fingerprint) -> Option<&MediaInfo> {
+        self.entries
+            .get(&fp.path)
+            .filter(|e| e.size == fp.size && e.modified == fp.modified)
+            .map(|e| &e.info)
+    }
+
+    pub fn insert(&mut self, fp: &Fingerprint, info: MediaInfo) {
+        let probed_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.entries.insert(
+            fp.path.clone(),
+            CacheEntry {
+                size: fp.size,
+                modified: fp.modified,
+                probed_at,
+                info,
+            },
+        );
+
+        while self.entries.len() > MAX_ENTRIES {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.probed_at)
+                .map(|(k, _)| k.clone())
+            else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+/// 读取缓存的媒体信息，未命中时调用 `probe` 探测并写入缓存
+///
+/// 无法读取文件元数据时（如路径不存在）直接调用 `probe`，由其报告错误。
+pub fn media_info(
+    path: &str,
+    probe: impl FnOnce(&str) -> Result<MediaInfo, String>,
+) -> Result<MediaInfo, String> {
+    let Some(fp) = Fingerprint::of(path) else {
+        return probe(path);
+    };
+
+    {
+        let mut guard = CACHE.lock().map_err(|e| format!("锁竞争错误: {e}"))?;
+        let cache = guard.get_or_insert_with(load_disk_cache);
+        if let Some(info) = cache.get(&fp) {
+            return Ok(info.clone());
+        }
+    }
+
+    // 探测期间不持有锁，避免阻塞其他文件的查询
+    let info = probe(path)?;
+
+    let mut guard = CACHE.lock().map_err(|e| format!("锁竞争错误: {e}"))?;
+    let cache = guard.get_or_insert_with(load_disk_cache);
+    cache.insert(&fp, info.clone());
+    save_disk_cache(cache);
+    Ok(info)
+}
+
+fn disk_cache_path() -> Option<PathBuf> {
+    let mut dir = dirs_next::cache_dir()?;
+    dir.push("ffsub");
+    dir.push("probe-cache.json");
+    Some(dir)
+}
+
+fn load_disk_cache() -> ProbeCache {
+    disk_cache_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn save_disk_cache(cache: &ProbeCache) {
+    let Some(path) = disk_cache_path() else {
+        return;
+    };
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| {
+            let json = serde_json::to_string(cache).map_err(std::io::Error::other)?;
+            std::fs::write(&path, json)
+        });
+    if let Err(e) = result {
+        log::warn!("写入探测缓存失败 {}: {e}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_invalidated_when_file_changes() {
+        let path = std::env::temp_dir().join(format!("ffsub-probe-cache-{}.mkv", std::process::id()));
+        std::fs::write(&path, b"first").unwrap();
+        let path_str = path.to_string_lossy().to_string();
+
+        let fp = Fingerprint::of(&path_str).unwrap();
+        let mut cache = ProbeCache::default();
+        let info = MediaInfo {
+            format_name: "matroska,webm".to_string(),
+            ..MediaInfo::default()
+        };
+        cache.insert(&fp, info.clone());
+        assert_eq!(cache.get(&fp), Some(&info));
+
+        std::fs::write(&path, b"changed content").unwrap();
+        let changed = Fingerprint::of(&path_str).unwrap();
+        assert_eq!(changed.path, fp.path);
+        assert_eq!(cache.get(&changed), None);
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(Fingerprint::of(&path_str), None);
+    }
+}
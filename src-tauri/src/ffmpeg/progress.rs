@@ -4,8 +4,10 @@ use crate::types::EncodeProgress;
 ///
 /// FFmpeg 进度输出格式示例:
 /// `frame= 1234 fps= 30.0 ... time=00:01:23.45 ... speed=2.5x`
+///
+/// 只处理音频时（如响度测量）没有 `frame=`，以 `size=` 开头。
 pub fn parse_progress_line(line: &str, total_duration_secs: f64) -> Option<EncodeProgress> {
-    if !line.contains("frame=") || !line.contains("time=") {
+    if !line.contains("time=") || !(line.contains("frame=") || line.contains("size=")) {
         return None;
     }
 
@@ -41,6 +43,8 @@ pub fn parse_progress_line(line: &str, total_duration_secs: f64) -> Option<Encod
         time,
         speed,
         percentage,
+        pass: 1,
+        total_passes: 1,
     })
 }
 
@@ -83,6 +87,15 @@ mod tests {
         assert!((progress.percentage - 40.0).abs() < 0.1);
     }
 
+    #[test]
+    fn test_parse_audio_only_progress() {
+        let line = "size=N/A time=00:00:05.00 bitrate=N/A speed= 412x";
+        let progress = parse_progress_line(line, 10.0).unwrap();
+        assert_eq!(progress.frame, 0);
+        assert!((progress.percentage - 50.0).abs() < 0.1);
+        assert!(parse_progress_line("Stream #0:1 -> #0:0 (truehd (native) -> pcm_s16le (native))", 10.0).is_none());
+    }
+
     #[test]
     fn test_parse_time() {
         assert!((parse_time_to_seconds("01:30:00.00") - 5400.0).abs() < f64::EPSILON);
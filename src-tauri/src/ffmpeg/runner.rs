@@ -1,35 +1,94 @@
+use std::collections::VecDeque;
 use std::io::{BufRead, BufReader};
-use std::process::{Command, Stdio};
-use std::time::Instant;
+use std::path::PathBuf;
+use std::process::{ChildStderr, Command, Stdio};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 use tauri::{AppHandle, Emitter, Manager};
 
-use crate::ffmpeg::args;
+use crate::ffmpeg::args::{self, ClipRange};
+use crate::ffmpeg::audio;
+use crate::ffmpeg::compat::{self, SourceCodecs};
+use crate::ffmpeg::encoder;
+use crate::ffmpeg::extra;
+use crate::ffmpeg::loudnorm;
+use crate::ffmpeg::{probe, probe_cache};
 use crate::ffmpeg::progress::parse_progress_line;
+use crate::ffmpeg::trim;
+use crate::ffmpeg::video;
+use crate::ffmpeg::x26x;
 use crate::state::AppState;
-use crate::types::{EncodeParams, VideoInfo};
+use crate::types::{
+    AudioSource, AudioTrack, EncodeParams, EncodeResult, EncoderChoice, EncoderFallback, LoudnessStats, LoudnormTarget, MediaInfo,
+    RateControl, VideoInfo,
+};
 
-/// 使用 ffprobe 探测视频总时长（秒）
+/// 按文件名模板生成输出文件路径，`{height}` 需要的源视频信息在这里探测
+pub fn resolve_output_path(params: &EncodeParams, index: usize) -> Result<String, String> {
+    let mut job = params.clone();
+    job.source_video = probe_media_info(&params.video_path)
+        .ok()
+        .and_then(|info| video::source_video(&info));
+    args::build_output_path(&job, index)
+}
+
+/// 探测视频总时长（秒）
 pub fn probe_duration(video_path: &str) -> Result<f64, String> {
-    let probe_args = args::build_probe_duration_args(video_path);
+    probe_media_info(video_path)?
+        .duration
+        .ok_or_else(|| "无法解析视频时长".to_string())
+}
 
-    let output = Command::new(args::ffprobe_bin())
-        .args(&probe_args)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .map_err(|e| format!("无法执行 ffprobe: {e}"))?;
+/// 估算输出中所有音轨的总码率（kbps）
+///
+/// 转码的音轨按指定码率或编码器默认码率计算；复制的音轨取源码率，
+/// 缺少码率信息（或无损转码）时按 192 kbps 估算。
+pub fn estimate_audio_bitrate(params: &EncodeParams) -> Result<u32, String> {
+    const FALLBACK_KBPS: u64 = 192;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    stdout
-        .trim()
-        .parse::<f64>()
-        .map_err(|_| "无法解析视频时长".to_string())
+    let info = probe_media_info(&params.video_path)?;
+    // 未指定音轨时 FFmpeg 默认只保留一条音频流
+    let streams = if params.audio_tracks.is_empty() {
+        audio::default_stream(&info).into_iter().collect()
+    } else {
+        audio::select_streams(&params.audio_tracks, &info)?
+    };
+    let global_kbps = params
+        .audio_codec
+        .as_deref()
+        .and_then(compat::default_audio_bitrate);
+
+    let total_bps: u64 = streams
+        .iter()
+        .enumerate()
+        .map(|(i, a)| {
+            let track_kbps = params
+                .audio_tracks
+                .get(i)
+                .and_then(|t| t.encode.as_ref())
+                .map(audio::encode_bitrate);
+            let kbps = match track_kbps {
+                Some(kbps) => kbps,
+                None => global_kbps,
+            };
+            match kbps {
+                Some(kbps) => u64::from(kbps) * 1000,
+                None => a.bit_rate.unwrap_or(FALLBACK_KBPS * 1000),
+            }
+        })
+        .sum();
+
+    Ok(total_bps.div_ceil(1000) as u32)
 }
 
-/// 使用 ffprobe 获取视频信息
-pub fn probe_video_info(video_path: &str) -> Result<VideoInfo, String> {
-    let probe_args = args::build_probe_info_args(video_path);
+/// 获取完整的媒体信息，文件未变化时使用缓存
+pub fn probe_media_info(video_path: &str) -> Result<MediaInfo, String> {
+    probe_cache::media_info(video_path, run_ffprobe)
+}
+
+/// 运行 ffprobe 读取所有流、章节和容器元数据
+fn run_ffprobe(video_path: &str) -> Result<MediaInfo, String> {
+    let probe_args = args::build_probe_media_args(video_path);
 
     let output = Command::new(args::ffprobe_bin())
         .args(&probe_args)
@@ -38,31 +97,89 @@ pub fn probe_video_info(video_path: &str) -> Result<VideoInfo, String> {
         .output()
         .map_err(|e| format!("无法执行 ffprobe: {e}"))?;
 
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("ffprobe 无法读取文件: {}", stderr.trim()));
+    }
+
     let stdout = String::from_utf8_lossy(&output.stdout);
     let json: serde_json::Value =
         serde_json::from_str(&stdout).map_err(|e| format!("解析 ffprobe 输出失败: {e}"))?;
+    Ok(probe::parse_media_info(&json))
+}
+
+/// 检查编码器能否输出任务要求的像素格式和 HDR，以及 x264/x265 高级选项
+fn validate_encoder(params: &EncodeParams, encoder: &str) -> Result<(), String> {
+    video::validate_output(params, encoder)?;
+    x26x::validate(params, encoder, video::output_format(params, encoder)?)
+}
+
+/// 检查画面处理设置，记录源画面信息，解析音轨选择并检查与目标容器的兼容性，应用自动修正（如不兼容音频转码），警告写入日志
+pub fn prepare_streams(params: &mut EncodeParams, encoder: &str) -> Result<(), String> {
+    video::validate(params)?;
+    extra::validate(&params.extra_args)?;
+    let info = probe_media_info(&params.video_path)?;
+    params.source_video = video::source_video(&info);
+    validate_encoder(params, encoder)?;
+    audio::resolve_tracks(params, &info)?;
+
+    // 多段剪辑的音频在滤镜图中截取，需要明确的输入流；沿用 FFmpeg 默认会选择的音轨
+    if params.trim.len() > 1
+        && params.audio_tracks.is_empty()
+        && let Some(stream) = audio::default_stream(&info)
+    {
+        params.audio_tracks.push(AudioTrack {
+            source: AudioSource::Index { index: stream.index },
+            default: true,
+            title: None,
+            encode: None,
+        });
+    }
+
+    let source = source_codecs(params, &info)?;
+    for issue in compat::resolve(params, encoder, &source)? {
+        log::warn!("{}", issue.message);
+    }
+    Ok(())
+}
+
+/// 将写入输出的各类流的编码格式
+///
+/// 音频只包含选中且直接复制的音轨；未指定音轨时 FFmpeg 只选一条，但无法预知是哪条，因此全部列出。
+pub fn source_codecs(params: &EncodeParams, info: &MediaInfo) -> Result<SourceCodecs, String> {
+    Ok(SourceCodecs {
+        video: info.video.iter().filter(|v| !v.attached_pic).map(|v| v.codec.clone()).collect(),
+        audio: audio::select_streams(&params.audio_tracks, info)?
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| params.audio_tracks.get(*i).is_none_or(|t| t.encode.is_none()))
+            .map(|(_, a)| a.codec.clone())
+            .collect(),
+        subtitle: info.subtitles.iter().map(|s| s.codec.clone()).collect(),
+    })
+}
+
+/// 获取视频概要信息
+pub fn probe_video_info(video_path: &str) -> Result<VideoInfo, String> {
+    let info = probe_media_info(video_path)?;
 
     // 优先使用扩展名，回退到 ffprobe format_name
     let format_name = std::path::Path::new(video_path)
         .extension()
         .and_then(|s| s.to_str())
         .map(|ext| ext.to_lowercase())
-        .unwrap_or_else(|| resolve_format_name(&json));
+        .unwrap_or_else(|| resolve_format_name(&info.format_name));
 
-    let duration_str = json["format"]["duration"]
-        .as_str()
-        .and_then(|d| d.parse::<f64>().ok())
+    let duration_str = info
+        .duration
         .map(format_duration)
         .unwrap_or_else(|| "-".into());
 
-    let resolution = json["streams"]
-        .as_array()
-        .and_then(|s| s.first())
-        .map(|s| {
-            let w = s["width"].as_u64().unwrap_or(0);
-            let h = s["height"].as_u64().unwrap_or(0);
-            format!("{w}x{h}")
-        })
+    let resolution = info
+        .video
+        .iter()
+        .find(|v| !v.attached_pic)
+        .map(|v| format!("{}x{}", v.width, v.height))
         .unwrap_or_else(|| "-".into());
 
     Ok(VideoInfo {
@@ -72,9 +189,8 @@ pub fn probe_video_info(video_path: &str) -> Result<VideoInfo, String> {
     })
 }
 
-/// 从 ffprobe JSON 中解析格式名称，按优先级选择
-fn resolve_format_name(json: &serde_json::Value) -> String {
-    let raw = json["format"]["format_name"].as_str().unwrap_or("unknown");
+/// 从 ffprobe format_name（如 `mov,mp4,m4a`）中按优先级选择格式名称
+fn resolve_format_name(raw: &str) -> String {
     let candidates: Vec<&str> = raw.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()).collect();
     const PREFERRED: &[&str] = &["mp4", "mkv", "mov", "webm", "avi", "flv", "wmv"];
     PREFERRED
@@ -84,101 +200,364 @@ fn resolve_format_name(json: &serde_json::Value) -> String {
         .unwrap_or_else(|| candidates.first().unwrap_or(&"unknown").to_lowercase())
 }
 
-/// 启动 FFmpeg 编码进程，并在后台线程中监控 stderr 解析进度
-pub fn spawn_encode(
+/// 渲染预览片段并等待完成，失败时返回 FFmpeg 最后几行错误输出
+///
+/// 子进程交由状态管理，可以像编码任务一样被停止。
+pub fn render_preview(
     params: &EncodeParams,
+    clip: ClipRange,
     output_path: &str,
     state: &AppState,
-    app_handle: &AppHandle,
 ) -> Result<(), String> {
-    let encode_args = args::build_encode_args(params, output_path);
+    const ERROR_LINES: usize = 5;
 
-    log::info!("执行: {} {}", args::ffmpeg_bin(), encode_args.join(" "));
+    let mut job = params.clone();
+    let encoder = args::select_encoder(&job);
+    prepare_streams(&mut job, &encoder)?;
+    let preview_args = args::build_preview_args(&job, &encoder, clip, output_path)?;
+
+    log::info!("执行预览: {} {}", args::ffmpeg_bin(), preview_args.join(" "));
 
     let mut child = Command::new(args::ffmpeg_bin())
-        .args(&encode_args)
-        .stdout(Stdio::piped())
+        .args(["-hide_banner", "-nostats"])
+        .args(&preview_args)
+        .stdout(Stdio::null())
         .stderr(Stdio::piped())
         .spawn()
         .map_err(|e| format!("启动 FFmpeg 失败: {e}"))?;
-
-    // 获取 stderr 用于进度解析
     let stderr = child
         .stderr
         .take()
         .ok_or_else(|| "无法获取 FFmpeg stderr".to_string())?;
-
     state.store_child(child)?;
 
-    // 后台线程：读取 stderr，解析进度并发送事件
+    let mut log_tail = VecDeque::with_capacity(ERROR_LINES);
+    for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+        if log_tail.len() == ERROR_LINES {
+            log_tail.pop_front();
+        }
+        log_tail.push_back(line);
+    }
+
+    match finish_pass(state, log_tail.into()) {
+        PassOutcome::Success => Ok(()),
+        PassOutcome::Stopped => Err("预览已取消".to_string()),
+        PassOutcome::Failed { message, log_tail } => {
+            Err(format!("预览渲染失败: {message}\n{}", log_tail.join("\n")))
+        }
+    }
+}
+
+/// 编码遍的参数：单遍编码时只有一遍，两遍编码的统计日志以 `log_prefix` 为前缀写在工作目录
+fn encode_passes(
+    params: &EncodeParams,
+    encoder: &str,
+    output_path: &str,
+    log_prefix: &str,
+) -> Result<Vec<Vec<String>>, String> {
+    if !args::needs_two_pass(params, encoder) {
+        return Ok(vec![args::build_encode_args(params, encoder, output_path)?]);
+    }
+    [1, 2]
+        .into_iter()
+        .map(|pass| {
+            args::build_pass_args(params, encoder, output_path, args::PassSpec { pass, log_prefix })
+        })
+        .collect()
+}
+
+/// 不执行的编码计划
+pub struct CommandPlan {
+    /// 实际使用的视频编码器
+    pub encoder: String,
+    /// 依次执行的各遍 FFmpeg 参数（不含程序名）
+    pub passes: Vec<Vec<String>>,
+    /// 与程序内编码的差异说明
+    pub notes: Vec<String>,
+}
+
+/// 生成与 [`spawn_encode`] 相同的命令而不执行
+///
+/// 响度测量值要到运行时才能得到，这里的编码遍使用单遍动态标准化代替。
+pub fn plan_commands(params: &EncodeParams, output_path: &str, log_prefix: &str) -> Result<CommandPlan, String> {
+    let duration = probe_duration(&params.video_path)?;
+    trim::validate_ranges(&params.trim, duration)?;
+    let mut job = params.clone();
+    let encoder = args::select_encoder(&job);
+    prepare_streams(&mut job, &encoder)?;
+    resolve_size_target(&mut job, trim::output_duration(&params.trim, duration))?;
+
+    let mut notes = Vec::new();
+    if job.loudnorm.is_some() {
+        notes.push("响度标准化使用单遍动态模式；程序内编码会先测量响度再做线性增益调整".to_string());
+    }
+    notes.extend(args::two_pass_note(&job, &encoder));
+    if args::needs_two_pass(&job, &encoder) {
+        notes.push(format!("两遍编码的统计日志 {log_prefix}* 写在当前目录，可在完成后删除"));
+    }
+    let passes = encode_passes(&job, &encoder, output_path, log_prefix)?;
+    Ok(CommandPlan { encoder, passes, notes })
+}
+
+/// 一次编码任务中依次执行的各遍 FFmpeg 调用
+struct PassPlan {
+    passes: Vec<Vec<String>>,
+    /// 两遍编码时 FFmpeg 的工作目录，统计日志写在这里
+    work_dir: Option<PathBuf>,
+    /// 统计日志文件名前缀，任务结束后据此清理
+    log_prefix: Option<String>,
+    /// 进度事件中本计划第一遍之前已完成的遍数（响度测量）
+    pass_offset: usize,
+    /// 进度事件中整个任务的总遍数
+    total_passes: usize,
+}
+
+impl PassPlan {
+    fn new(params: &EncodeParams, encoder: &str, output_path: &str) -> Result<Self, String> {
+        // 响度测量遍在编码计划之前执行，计入总遍数
+        let pass_offset = params.loudness.len();
+
+        if !args::needs_two_pass(params, encoder) {
+            return Ok(Self {
+                passes: vec![args::build_encode_args(params, encoder, output_path)?],
+                work_dir: None,
+                log_prefix: None,
+                pass_offset,
+                total_passes: pass_offset + 1,
+            });
+        }
+
+        // 统计日志使用相对路径，避免 Windows 盘符中的冒号破坏 `-x265-params` 的解析
+        let work_dir = std::env::temp_dir().join("ffsub-passlog");
+        std::fs::create_dir_all(&work_dir).map_err(|e| format!("创建临时目录失败: {e}"))?;
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        let log_prefix = format!("ffsub-{}-{stamp}", std::process::id());
+
+        let passes = encode_passes(params, encoder, output_path, &log_prefix)?;
+
+        Ok(Self {
+            total_passes: pass_offset + passes.len(),
+            passes,
+            work_dir: Some(work_dir),
+            log_prefix: Some(log_prefix),
+            pass_offset,
+        })
+    }
+
+    /// 响度测量计划：每条输出音轨一遍，总遍数包含随后的编码遍
+    fn loudness(params: &EncodeParams, target: &LoudnormTarget, encoder: &str) -> Self {
+        let passes = loudnorm::measure_args(params, target);
+        let encode_passes = if args::needs_two_pass(params, encoder) { 2 } else { 1 };
+        Self {
+            total_passes: passes.len() + encode_passes,
+            passes,
+            work_dir: None,
+            log_prefix: None,
+            pass_offset: 0,
+        }
+    }
+
+    /// 删除两遍编码产生的统计日志（如 `-0.log`、`.mbtree`、`.log.cutree`）
+    fn cleanup(&self) {
+        let (Some(dir), Some(prefix)) = (&self.work_dir, &self.log_prefix) else {
+            return;
+        };
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy().starts_with(prefix.as_str())
+                && let Err(e) = std::fs::remove_file(entry.path())
+            {
+                log::warn!("清理统计日志失败: {e}");
+            }
+        }
+    }
+}
+
+/// 一遍编码结束后的状态
+enum PassOutcome {
+    Success,
+    /// 子进程已被外部取走（如 stop）
+    Stopped,
+    Failed {
+        message: String,
+        /// 失败那一遍最后若干行非进度输出，用于判断失败原因
+        log_tail: Vec<String>,
+    },
+}
+
+impl PassOutcome {
+    fn failed(message: String) -> Self {
+        Self::Failed {
+            message,
+            log_tail: Vec::new(),
+        }
+    }
+}
+
+/// 每遍保留的非进度输出行数
+const LOG_TAIL_LINES: usize = 30;
+
+/// 目标大小模式下输出超出目标时的最大修正重试次数
+const MAX_SIZE_RETRIES: u32 = 2;
+
+/// 目标大小模式的换算结果
+#[derive(Debug, Clone, Copy)]
+pub struct SizeTarget {
+    bytes: u64,
+    bitrate: u32,
+}
+
+/// 将目标大小模式换算为两遍编码的视频码率，其他模式原样返回
+pub fn resolve_size_target(params: &mut EncodeParams, duration: f64) -> Result<Option<SizeTarget>, String> {
+    let RateControl::TargetSize {
+        size_mb,
+        audio_bitrate,
+    } = params.rate_control
+    else {
+        return Ok(None);
+    };
+
+    let audio_kbps = match audio_bitrate {
+        Some(kbps) => kbps,
+        None => estimate_audio_bitrate(params)?,
+    };
+    let bitrate = args::target_video_bitrate(size_mb, duration, audio_kbps)?;
+    log::info!("目标大小 {size_mb} MB：音频 {audio_kbps} kbps，视频 {bitrate} kbps");
+
+    params.rate_control = RateControl::TwoPass { bitrate };
+    Ok(Some(SizeTarget {
+        bytes: (size_mb * 1_000_000.0) as u64,
+        bitrate,
+    }))
+}
+
+/// 启动编码任务：同步启动第一遍 FFmpeg，随后在后台线程中依次监控每一遍的进度
+///
+/// 返回实际使用的视频编码器。
+pub fn spawn_encode(
+    params: &EncodeParams,
+    output_path: &str,
+    state: &AppState,
+    app_handle: &AppHandle,
+) -> Result<String, String> {
     let total_duration = state.get_total_duration()?;
+    let mut job = params.clone();
+    let encoder = args::select_encoder(&job);
+    prepare_streams(&mut job, &encoder)?;
+    let size_target = resolve_size_target(&mut job, total_duration)?;
+
+    // 启用响度标准化时先执行测量遍，编码参数要等测量完成后才能生成
+    let plan = match &job.loudnorm {
+        Some(target) => PassPlan::loudness(&job, target, &encoder),
+        None => PassPlan::new(&job, &encoder, output_path)?,
+    };
+    let stderr = match spawn_pass(&plan, 0, state) {
+        Ok(stderr) => stderr,
+        Err(e) => {
+            plan.cleanup();
+            return Err(e);
+        }
+    };
+
+    // 后台线程：读取 stderr，解析进度并发送事件
     let handle = app_handle.clone();
     let output_path_owned = output_path.to_string();
+    let encoder_owned = encoder.clone();
 
     std::thread::spawn(move || {
-        let mut reader = BufReader::new(stderr);
-        let mut line_buf = String::new();
-        // 进度事件节流：至少间隔 200ms 发送一次，避免淹没前端
-        let mut last_progress_emit = Instant::now();
-        let throttle_interval = std::time::Duration::from_millis(200);
-
-        loop {
-            line_buf.clear();
-            match reader.read_line(&mut line_buf) {
-                Ok(0) => break,
-                Ok(_) => {
-                    // FFmpeg 进度行可能包含 \r
-                    for segment in line_buf.split('\r') {
-                        let trimmed = segment.trim();
-                        if trimmed.is_empty() {
-                            continue;
-                        }
-
-                        // 尝试解析进度，节流发送
-                        if let Some(progress) =
-                            parse_progress_line(trimmed, total_duration)
-                        {
-                            let now = Instant::now();
-                            if now.duration_since(last_progress_emit) >= throttle_interval {
-                                let _ = handle.emit("encode-progress", &progress);
-                                last_progress_emit = now;
-                            }
-                        } else {
-                            // 仅非进度行才发送日志，减少事件量
-                            let _ = handle.emit("encode-log", trimmed.to_string());
-                        }
-                    }
+        let state = handle.state::<AppState>();
+        let mut encoder = encoder_owned;
+        let mut outcome = if job.loudnorm.is_some() {
+            match measure_loudness(&job, &plan, stderr, &state, &handle, total_duration) {
+                Ok(loudness) => {
+                    job.loudness = loudness;
+                    run_plan(&job, &encoder, &output_path_owned, &state, &handle, total_duration)
                 }
-                Err(_) => break,
+                Err(outcome) => outcome,
             }
+        } else {
+            let outcome = run_passes(&plan, Some(stderr), &state, &handle, total_duration);
+            plan.cleanup();
+            outcome
+        };
+
+        // 自动选择的硬件编码器初始化失败（驱动不匹配、消费级 NVENC 会话数用尽等）时改用对应的软件编码器重跑；
+        // 用户指定的编码器直接报告失败
+        let mut fallback = None;
+        if let PassOutcome::Failed { log_tail, .. } = &outcome
+            && job.hw_fallback
+            && job.encoder_choice == EncoderChoice::Auto
+            && let Some(software) = encoder::software_equivalent(&encoder)
+            && let Some(reason) = encoder::hw_init_failure(log_tail)
+        {
+            let msg = format!("硬件编码器 {encoder} 初始化失败（{reason}），改用 {software} 重新编码");
+            log::warn!("{msg}");
+            let _ = handle.emit("encode-log", msg);
+
+            fallback = Some(EncoderFallback {
+                from: encoder.clone(),
+                to: software.to_string(),
+                reason,
+            });
+            encoder = software.to_string();
+            // 开始时只按硬件编码器检查过，x264/x265 高级选项等需按回退的编码器重新检查
+            outcome = match validate_encoder(&job, &encoder) {
+                Ok(()) => run_plan(&job, &encoder, &output_path_owned, &state, &handle, total_duration),
+                Err(e) => PassOutcome::failed(format!("无法改用 {encoder} 编码: {e}")),
+            };
         }
 
-        // 进程结束后，取回子进程并等待其退出，以判断成功或失败
-        // 注意：此时 child 可能已被 stop 取走
-        let state = handle.state::<crate::state::AppState>();
-        match state.take_child() {
-            Ok(Some(mut child)) => {
-                match child.wait() {
-                    Ok(status) => {
-                        if status.success() {
-                            let _ = handle.emit("encode-complete", &output_path_owned);
-                        } else {
-                            let code = status.code();
-                            let msg = format!("FFmpeg 进程异常退出: code={:?}", code);
-                            let _ = handle.emit("encode-error", msg);
-                        }
-                    }
-                    Err(e) => {
-                        let _ = handle.emit("encode-error", format!("等待 FFmpeg 退出失败: {e}"));
-                    }
+        let mut attempts = 1;
+        let mut bitrate = size_target.map(|t| t.bitrate);
+
+        // 目标大小模式：超出目标时按实际比例下调码率并重新两遍编码
+        if let Some(target) = size_target {
+            while matches!(outcome, PassOutcome::Success) && attempts <= MAX_SIZE_RETRIES {
+                let Some(actual) = file_size(&output_path_owned) else {
+                    break;
+                };
+                if actual <= target.bytes {
+                    break;
                 }
+
+                let current = bitrate.unwrap_or(target.bitrate);
+                let next = args::corrected_bitrate(current, target.bytes, actual);
+                let msg = format!(
+                    "输出 {actual} 字节超出目标 {} 字节，视频码率 {current} → {next} kbps 重新编码",
+                    target.bytes
+                );
+                log::info!("{msg}");
+                let _ = handle.emit("encode-log", msg);
+
+                job.rate_control = RateControl::TwoPass { bitrate: next };
+                bitrate = Some(next);
+                attempts += 1;
+
+                outcome = run_plan(&job, &encoder, &output_path_owned, &state, &handle, total_duration);
             }
-            Ok(None) => {
-                // 子进程已被外部取走（如 stop），这里仍然通知完成以便前端更新状态
-                let _ = handle.emit("encode-complete", &output_path_owned);
+        }
+
+        match outcome {
+            // 被停止时仍然通知完成以便前端更新状态
+            PassOutcome::Success | PassOutcome::Stopped => {
+                let result = EncodeResult {
+                    size_bytes: file_size(&output_path_owned),
+                    output_path: output_path_owned,
+                    encoder,
+                    video_bitrate: bitrate,
+                    attempts,
+                    fallback,
+                    loudness: job.loudness,
+                };
+                let _ = handle.emit("encode-complete", &result);
             }
-            Err(e) => {
-                let _ = handle.emit("encode-error", format!("获取子进程失败: {e}"));
+            PassOutcome::Failed { message, .. } => {
+                let _ = handle.emit("encode-error", message);
             }
         }
 
@@ -186,7 +565,219 @@ pub fn spawn_encode(
         let _ = state.set_running(false);
     });
 
-    Ok(())
+    Ok(encoder)
+}
+
+/// 按当前参数和编码器重新规划并执行整个任务，结束后清理统计日志
+fn run_plan(
+    job: &EncodeParams,
+    encoder: &str,
+    output_path: &str,
+    state: &AppState,
+    handle: &AppHandle,
+    total_duration: f64,
+) -> PassOutcome {
+    let plan = match PassPlan::new(job, encoder, output_path) {
+        Ok(plan) => plan,
+        Err(e) => return PassOutcome::failed(e),
+    };
+    let outcome = run_passes(&plan, None, state, handle, total_duration);
+    plan.cleanup();
+    outcome
+}
+
+/// 依次执行计划中的每一遍；`first_stderr` 为已同步启动的第一遍，为空时由这里启动
+fn run_passes(
+    plan: &PassPlan,
+    first_stderr: Option<ChildStderr>,
+    state: &AppState,
+    handle: &AppHandle,
+    total_duration: f64,
+) -> PassOutcome {
+    let mut stderr = first_stderr;
+
+    for index in 0..plan.passes.len() {
+        let pass_stderr = match stderr.take() {
+            Some(s) => s,
+            // 两遍之间用户可能已请求停止
+            None if !state.is_running().unwrap_or(false) => return PassOutcome::Stopped,
+            None => match spawn_pass(plan, index, state) {
+                Ok(s) => s,
+                Err(e) => return PassOutcome::failed(e),
+            },
+        };
+
+        let pass = plan.pass_offset + index + 1;
+        let log_tail = monitor_pass(pass_stderr, handle, total_duration, pass, plan.total_passes);
+
+        let outcome = finish_pass(state, log_tail);
+        if !matches!(outcome, PassOutcome::Success) {
+            return outcome;
+        }
+    }
+
+    PassOutcome::Success
+}
+
+/// 执行响度测量计划，解析每条音轨的统计值；测量失败或被停止时返回对应的结果
+fn measure_loudness(
+    job: &EncodeParams,
+    plan: &PassPlan,
+    first_stderr: ChildStderr,
+    state: &AppState,
+    handle: &AppHandle,
+    total_duration: f64,
+) -> Result<Vec<LoudnessStats>, PassOutcome> {
+    // 源采样率用于标准化后恢复；未指定音轨时无法预知 FFmpeg 选中哪条，使用默认值
+    let sample_rates: Vec<Option<u32>> = match probe_media_info(&job.video_path) {
+        Ok(info) => audio::select_streams(&job.audio_tracks, &info)
+            .map(|streams| streams.iter().map(|s| s.sample_rate).collect())
+            .unwrap_or_default(),
+        Err(_) => Vec::new(),
+    };
+
+    let mut results = Vec::with_capacity(plan.passes.len());
+    let mut stderr = Some(first_stderr);
+
+    for index in 0..plan.passes.len() {
+        let pass_stderr = match stderr.take() {
+            Some(s) => s,
+            None if !state.is_running().unwrap_or(false) => return Err(PassOutcome::Stopped),
+            None => spawn_pass(plan, index, state).map_err(PassOutcome::failed)?,
+        };
+
+        let log_tail = monitor_pass(pass_stderr, handle, total_duration, index + 1, plan.total_passes);
+        match finish_pass(state, log_tail.clone()) {
+            PassOutcome::Success => {}
+            outcome => return Err(outcome),
+        }
+
+        let mut stats = loudnorm::parse_stats(&log_tail).map_err(PassOutcome::failed)?;
+        if !job.audio_tracks.is_empty() {
+            stats.sample_rate = sample_rates.get(index).copied().flatten();
+        }
+        let msg = if loudnorm::is_measurable(&stats) {
+            format!(
+                "音轨 {} 响度: {:.1} LUFS，真峰值 {:.1} dBTP，响度范围 {:.1} LU",
+                index + 1,
+                stats.input_i,
+                stats.input_tp,
+                stats.input_lra
+            )
+        } else {
+            format!("音轨 {} 为静音，跳过响度标准化", index + 1)
+        };
+        log::info!("{msg}");
+        let _ = handle.emit("encode-log", msg);
+        results.push(stats);
+    }
+
+    Ok(results)
+}
+
+fn file_size(path: &str) -> Option<u64> {
+    std::fs::metadata(path).map(|m| m.len()).ok()
+}
+
+/// 启动计划中的第 `index` 遍，子进程交由状态管理以便随时停止，返回其 stderr
+fn spawn_pass(plan: &PassPlan, index: usize, state: &AppState) -> Result<ChildStderr, String> {
+    let pass_args = &plan.passes[index];
+
+    log::info!("执行: {} {}", args::ffmpeg_bin(), pass_args.join(" "));
+
+    let mut command = Command::new(args::ffmpeg_bin());
+    command
+        .args(pass_args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(dir) = &plan.work_dir {
+        command.current_dir(dir);
+    }
+
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("启动 FFmpeg 失败: {e}"))?;
+
+    // 获取 stderr 用于进度解析
+    let stderr = child
+        .stderr
+        .take()
+        .ok_or_else(|| "无法获取 FFmpeg stderr".to_string())?;
+
+    state.store_child(child)?;
+    Ok(stderr)
+}
+
+/// 读取一遍编码的 stderr 直到进程关闭输出，解析进度并节流发送事件
+///
+/// 返回最后若干行非进度输出。
+fn monitor_pass(
+    stderr: ChildStderr,
+    handle: &AppHandle,
+    total_duration: f64,
+    pass: usize,
+    total_passes: usize,
+) -> Vec<String> {
+    let mut reader = BufReader::new(stderr);
+    let mut line_buf = String::new();
+    let mut log_tail = VecDeque::with_capacity(LOG_TAIL_LINES);
+    // 进度事件节流：至少间隔 200ms 发送一次，避免淹没前端
+    let mut last_progress_emit = Instant::now();
+    let throttle_interval = std::time::Duration::from_millis(200);
+
+    loop {
+        line_buf.clear();
+        match reader.read_line(&mut line_buf) {
+            Ok(0) => break,
+            Ok(_) => {
+                // FFmpeg 进度行可能包含 \r
+                for segment in line_buf.split('\r') {
+                    let trimmed = segment.trim();
+                    if trimmed.is_empty() {
+                        continue;
+                    }
+
+                    // 尝试解析进度，节流发送
+                    if let Some(mut progress) = parse_progress_line(trimmed, total_duration) {
+                        let now = Instant::now();
+                        if now.duration_since(last_progress_emit) >= throttle_interval {
+                            progress.pass = pass as u32;
+                            progress.total_passes = total_passes as u32;
+                            let _ = handle.emit("encode-progress", &progress);
+                            last_progress_emit = now;
+                        }
+                    } else {
+                        // 仅非进度行才发送日志，减少事件量
+                        let _ = handle.emit("encode-log", trimmed.to_string());
+                        if log_tail.len() == LOG_TAIL_LINES {
+                            log_tail.pop_front();
+                        }
+                        log_tail.push_back(trimmed.to_string());
+                    }
+                }
+            }
+            Err(_) => break,
+        }
+    }
+
+    log_tail.into()
+}
+
+/// 进程关闭输出后，取回子进程并等待其退出，以判断成功或失败
+/// 注意：此时 child 可能已被 stop 取走
+fn finish_pass(state: &AppState, log_tail: Vec<String>) -> PassOutcome {
+    match state.take_child() {
+        Ok(Some(mut child)) => match child.wait() {
+            Ok(status) if status.success() => PassOutcome::Success,
+            Ok(status) => PassOutcome::Failed {
+                message: format!("FFmpeg 进程异常退出: code={:?}", status.code()),
+                log_tail,
+            },
+            Err(e) => PassOutcome::failed(format!("等待 FFmpeg 退出失败: {e}")),
+        },
+        Ok(None) => PassOutcome::Stopped,
+        Err(e) => PassOutcome::failed(format!("获取子进程失败: {e}")),
+    }
 }
 
 /// 格式化秒数为 HH:MM:SS
@@ -0,0 +1,124 @@
+use std::path::Path;
+
+/// 单条字幕的起止时间（秒）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cue {
+    pub start: f64,
+    pub end: f64,
+}
+
+/// 读取字幕文件中所有条目的时间轴，按文件中的出现顺序返回
+///
+/// 仅解析时间戳，时间戳均为 ASCII 字符，因此 GBK/Big5 编码的文件也可以按字节宽松解码。
+pub fn read_cues(path: &str) -> Result<Vec<Cue>, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("读取字幕文件失败: {e}"))?;
+    let text = String::from_utf8_lossy(&bytes);
+
+    let ext = Path::new(path)
+        .extension()
+        .and_then(|s| s.to_str())
+        .map(|s| s.to_lowercase())
+        .unwrap_or_default();
+
+    let cues = match ext.as_str() {
+        "ass" | "ssa" => parse_ass_cues(&text),
+        _ => parse_srt_cues(&text),
+    };
+
+    if cues.is_empty() {
+        return Err("字幕文件中没有可识别的时间轴".to_string());
+    }
+    Ok(cues)
+}
+
+/// 解析 SRT / WebVTT 时间轴行：`00:00:01,000 --> 00:00:02,500`
+fn parse_srt_cues(text: &str) -> Vec<Cue> {
+    text.lines()
+        .filter_map(|line| {
+            let (start, rest) = line.split_once("-->")?;
+            // VTT 时间轴后可能跟随位置设置，如 `align:start`
+            let end = rest.split_whitespace().next()?;
+            Some(Cue {
+                start: parse_timestamp(start.trim())?,
+                end: parse_timestamp(end)?,
+            })
+        })
+        .collect()
+}
+
+/// 解析 ASS/SSA `[Events]` 段中的 Dialogue 行，字段顺序由 `Format:` 行决定
+fn parse_ass_cues(text: &str) -> Vec<Cue> {
+    let mut in_events = false;
+    // 默认字段位置与 ASS v4+ 规范一致
+    let mut start_idx = 1;
+    let mut end_idx = 2;
+    let mut cues = Vec::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_events = line.eq_ignore_ascii_case("[events]");
+            continue;
+        }
+        if !in_events {
+            continue;
+        }
+
+        if let Some(format) = line.strip_prefix("Format:") {
+            let fields: Vec<&str> = format.split(',').map(|f| f.trim()).collect();
+            if let Some(i) = fields.iter().position(|f| f.eq_ignore_ascii_case("start")) {
+                start_idx = i;
+            }
+            if let Some(i) = fields.iter().position(|f| f.eq_ignore_ascii_case("end")) {
+                end_idx = i;
+            }
+        } else if let Some(dialogue) = line.strip_prefix("Dialogue:") {
+            let fields: Vec<&str> = dialogue.splitn(10, ',').map(|f| f.trim()).collect();
+            let start = fields.get(start_idx).and_then(|t| parse_timestamp(t));
+            let end = fields.get(end_idx).and_then(|t| parse_timestamp(t));
+            if let (Some(start), Some(end)) = (start, end) {
+                cues.push(Cue { start, end });
+            }
+        }
+    }
+
+    cues
+}
+
+/// 解析 `H:MM:SS.cc`、`HH:MM:SS,mmm`、`MM:SS.mmm` 等格式的时间戳
+fn parse_timestamp(s: &str) -> Option<f64> {
+    let normalized = s.replace(',', ".");
+    let parts: Vec<&str> = normalized.split(':').collect();
+    let (h, m, sec) = match parts.as_slice() {
+        [h, m, s] => (h.parse::<f64>().ok()?, m.parse::<f64>().ok()?, s.parse::<f64>().ok()?),
+        [m, s] => (0.0, m.parse::<f64>().ok()?, s.parse::<f64>().ok()?),
+        _ => return None,
+    };
+    Some(h * 3600.0 + m * 60.0 + sec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_srt_and_vtt() {
+        let srt = "1\n00:00:01,000 --> 00:00:02,500\n你好\n\n2\n00:01:00,000 --> 00:01:03,000\nHi\n";
+        assert_eq!(
+            parse_srt_cues(srt),
+            vec![Cue { start: 1.0, end: 2.5 }, Cue { start: 60.0, end: 63.0 }]
+        );
+
+        let vtt = "WEBVTT\n\n00:05.000 --> 00:06.000 align:start\nText\n";
+        assert_eq!(parse_srt_cues(vtt), vec![Cue { start: 5.0, end: 6.0 }]);
+    }
+
+    #[test]
+    fn test_parse_ass_respects_format_line() {
+        let ass = "[Script Info]\nTitle: x\n\n[Events]\n\
+                   Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+                   Dialogue: 0,0:00:10.50,0:00:12.00,Default,,0,0,0,,{\\k20}a,b,c\n\
+                   Comment: 0,0:00:20.00,0:00:21.00,Default,,0,0,0,,skip\n";
+        assert_eq!(parse_ass_cues(ass), vec![Cue { start: 10.5, end: 12.0 }]);
+    }
+}
@@ -0,0 +1,274 @@
+//! 输出文件名模板
+//!
+//! 模板是相对于输出目录的路径，`/` 或 `\` 分隔子目录，`{name}` 替换为变量，`{{`、`}}` 表示花括号本身。
+//! 变量值中的路径分隔符和各平台的非法字符替换为 `_`，不能借变量创建子目录或跳出输出目录。
+//! 文件名不以输出格式扩展名结尾时自动补上。
+
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 未指定模板时的文件名，与旧版本一致
+pub const DEFAULT_TEMPLATE: &str = "{stem}_sub.{ext}";
+
+/// 单个文件名的最大长度（字符）
+const MAX_NAME_LEN: usize = 200;
+
+/// Windows 保留的设备名
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// 模板变量
+#[derive(Debug, Clone, Default)]
+pub struct OutputVars {
+    /// 源视频文件名（不含扩展名）
+    pub stem: String,
+    /// 输出格式扩展名
+    pub ext: String,
+    /// 视频编码格式，如 `hevc`
+    pub codec: String,
+    pub crf: i32,
+    /// 输出画面高度，未知时使用 `{height}` 报错
+    pub height: Option<u32>,
+    /// 字幕语言，从字幕文件名识别，无法识别时为空
+    pub lang: String,
+    /// 日期 `YYYY-MM-DD`（UTC）
+    pub date: String,
+    /// 任务序号，从 1 开始
+    pub index: usize,
+}
+
+impl OutputVars {
+    fn get(&self, name: &str) -> Result<String, String> {
+        Ok(match name {
+            "stem" => self.stem.clone(),
+            "ext" => self.ext.clone(),
+            "codec" => self.codec.clone(),
+            "crf" => self.crf.to_string(),
+            "height" => self
+                .height
+                .ok_or("无法获取视频高度，不能使用 {height}")?
+                .to_string(),
+            "lang" => self.lang.clone(),
+            "date" => self.date.clone(),
+            "index" => self.index.to_string(),
+            _ => return Err(format!("未知的模板变量: {{{name}}}")),
+        })
+    }
+}
+
+/// 把模板展开为相对路径
+pub fn render(template: &str, vars: &OutputVars) -> Result<PathBuf, String> {
+    let template = template.trim();
+    if template.is_empty() {
+        return Err("文件名模板为空".to_string());
+    }
+    if template.starts_with(['/', '\\']) || template.as_bytes().get(1) == Some(&b':') {
+        return Err("文件名模板必须是相对路径".to_string());
+    }
+
+    let mut components = Vec::new();
+    let mut current = String::new();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '/' | '\\' => components.push(std::mem::take(&mut current)),
+            '{' => {
+                if chars.as_str().starts_with('{') {
+                    chars.next();
+                    current.push('{');
+                    continue;
+                }
+                let rest = chars.as_str();
+                let end = rest.find('}').ok_or("文件名模板中的 { 未闭合")?;
+                let value = vars.get(&rest[..end])?;
+                current.push_str(&replace_illegal(&value));
+                chars = rest[end + 1..].chars();
+            }
+            '}' => {
+                if !chars.as_str().starts_with('}') {
+                    return Err("文件名模板中有多余的 }".to_string());
+                }
+                chars.next();
+                current.push('}');
+            }
+            c => current.push(c),
+        }
+    }
+    components.push(current);
+
+    let last = components.len() - 1;
+    let mut path = PathBuf::new();
+    for (i, component) in components.iter().enumerate() {
+        let mut name = sanitize(component);
+        if i == last && !vars.ext.is_empty() {
+            let suffix = format!(".{}", vars.ext);
+            if !name.to_lowercase().ends_with(&suffix.to_lowercase()) {
+                name.push_str(&suffix);
+            }
+        }
+        if name.is_empty() || name == "." || name == ".." || name == format!(".{}", vars.ext) {
+            return Err(format!("文件名模板展开后有空的路径段: {template}"));
+        }
+        path.push(name);
+    }
+    Ok(path)
+}
+
+/// 替换路径分隔符、各平台的非法字符和控制字符
+fn replace_illegal(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_control() || matches!(c, '/' | '\\' | '<' | '>' | ':' | '"' | '|' | '?' | '*') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// 清理单个路径段：替换非法字符，截断过长的名称，去掉 Windows 不允许的结尾点号和空格，避开保留设备名
+fn sanitize(component: &str) -> String {
+    let name: String = replace_illegal(component)
+        .chars()
+        .take(MAX_NAME_LEN)
+        .collect();
+    let mut name = name.trim().trim_end_matches(['.', ' ']).to_string();
+    let base = name.split('.').next().unwrap_or_default();
+    if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(base)) {
+        name.insert(0, '_');
+    }
+    name
+}
+
+/// 编码器对应的编码格式名，如 `libx265`、`hevc_nvenc` → `hevc`
+pub fn codec_name(encoder: &str) -> String {
+    let name = match encoder {
+        "libx264" => "h264",
+        "libx265" => "hevc",
+        "libsvtav1" | "libaom-av1" | "librav1e" => "av1",
+        "libvpx-vp9" => "vp9",
+        "libvpx" => "vp8",
+        other => other.split('_').next().unwrap_or(other),
+    };
+    name.to_string()
+}
+
+/// 从字幕文件名识别语言，如 `movie.zh-Hans.ass` → `zh-Hans`、`movie.eng.srt` → `eng`
+pub fn subtitle_language(subtitle_path: &str) -> String {
+    let stem = std::path::Path::new(subtitle_path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    let Some((_, tag)) = stem.rsplit_once('.') else {
+        return String::new();
+    };
+    let mut parts = tag.split(['-', '_']);
+    let primary = parts.next().unwrap_or_default();
+    let is_language = (2..=3).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_alphabetic())
+        && parts
+            .all(|p| (2..=4).contains(&p.len()) && p.chars().all(|c| c.is_ascii_alphanumeric()));
+    if is_language {
+        tag.to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// 当前 UTC 日期 `YYYY-MM-DD`
+pub fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    date_from_days((secs / 86400) as i64)
+}
+
+/// 1970-01-01 起的天数转换为公历日期
+fn date_from_days(days: i64) -> String {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> OutputVars {
+        OutputVars {
+            stem: "Show: S01E02?".to_string(),
+            ext: "mkv".to_string(),
+            codec: "hevc".to_string(),
+            crf: 20,
+            height: Some(1080),
+            lang: "zh-Hans".to_string(),
+            date: "2024-03-01".to_string(),
+            index: 3,
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let vars = vars();
+        assert_eq!(
+            render(DEFAULT_TEMPLATE, &vars).unwrap(),
+            PathBuf::from("Show_ S01E02__sub.mkv")
+        );
+        assert_eq!(
+            render(
+                "{date}/{index}-{stem}.{lang}.{codec}-crf{crf}-{height}p",
+                &vars
+            )
+            .unwrap(),
+            ["2024-03-01", "3-Show_ S01E02_.zh-Hans.hevc-crf20-1080p.mkv"]
+                .iter()
+                .collect::<PathBuf>()
+        );
+        assert_eq!(
+            render("{{{crf}}}", &vars).unwrap(),
+            PathBuf::from("{20}.mkv")
+        );
+    }
+
+    #[test]
+    fn test_render_rejects_escapes() {
+        let mut vars = vars();
+        vars.stem = "../../etc/passwd".to_string();
+        assert_eq!(
+            render("{stem}", &vars).unwrap(),
+            PathBuf::from(".._.._etc_passwd.mkv")
+        );
+        assert!(render("../{stem}", &vars).is_err());
+        assert!(render("/tmp/{stem}", &vars).is_err());
+        assert!(render("C:/{stem}", &vars).is_err());
+        assert!(render("{unknown}", &vars).is_err());
+        assert!(render("{stem", &vars).is_err());
+        assert!(render("out//{stem}", &vars).is_err());
+        assert_eq!(render("con", &vars).unwrap(), PathBuf::from("_con.mkv"));
+        vars.height = None;
+        assert!(render("{height}p", &vars).is_err());
+    }
+
+    #[test]
+    fn test_helpers() {
+        assert_eq!(subtitle_language("/subs/movie.zh-Hans.ass"), "zh-Hans");
+        assert_eq!(subtitle_language("movie.eng.srt"), "eng");
+        assert_eq!(subtitle_language("movie.ass"), "");
+        assert_eq!(subtitle_language("movie.final.ass"), "");
+        assert_eq!(codec_name("hevc_nvenc"), "hevc");
+        assert_eq!(codec_name("libsvtav1"), "av1");
+        assert_eq!(date_from_days(0), "1970-01-01");
+        assert_eq!(date_from_days(19_783), "2024-03-01");
+    }
+}
@@ -0,0 +1,239 @@
+//! 剪辑范围
+//!
+//! 只保留一段时使用输入端 seek（`-ss`/`-t`），字幕滤镜前后用 `setpts` 恢复并归零时间戳；
+//! 保留多段时在 `-filter_complex` 中先按原时间轴烧录字幕，再用 `trim`/`atrim` 截取各段并 `concat`。
+
+use crate::ffmpeg::filter::{Filter, FilterChain, FilterGraph};
+use crate::types::TimeRange;
+
+/// 检查剪辑范围：起点非负，终点晚于起点，各段按时间顺序排列且互不重叠
+pub fn validate_ranges(ranges: &[TimeRange], total: f64) -> Result<(), String> {
+    let mut previous_end = 0.0;
+    for (i, range) in ranges.iter().enumerate() {
+        let n = i + 1;
+        if !range.start.is_finite() || range.start < 0.0 {
+            return Err(format!("第 {n} 段起点无效: {}", range.start));
+        }
+        if let Some(end) = range.end
+            && (end.is_nan() || end <= range.start)
+        {
+            return Err(format!("第 {n} 段终点 {end} 必须晚于起点 {}", range.start));
+        }
+        if total > 0.0 && range.start >= total {
+            return Err(format!("第 {n} 段起点 {} 超出视频时长 {total:.3}", range.start));
+        }
+        if i > 0 && range.start < previous_end {
+            return Err(format!("第 {n} 段与上一段重叠或顺序颠倒"));
+        }
+        match range.end {
+            Some(end) => previous_end = end,
+            // 没有终点的一段持续到视频结尾，只能是最后一段
+            None if n < ranges.len() => return Err(format!("只有最后一段可以省略终点（第 {n} 段）")),
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// 剪辑后的输出时长（秒），用于计算进度；总时长未知且有段落持续到结尾时返回 0
+pub fn output_duration(ranges: &[TimeRange], total: f64) -> f64 {
+    if ranges.is_empty() {
+        return total;
+    }
+    let mut sum = 0.0;
+    for range in ranges {
+        let end = match range.end {
+            Some(end) if total > 0.0 => end.min(total),
+            Some(end) => end,
+            None if total > 0.0 => total,
+            None => return 0.0,
+        };
+        sum += (end - range.start).max(0.0);
+    }
+    sum
+}
+
+/// 只保留一段时返回该段，使用输入端 seek
+pub fn single_range(ranges: &[TimeRange]) -> Option<TimeRange> {
+    match ranges {
+        [range] => Some(*range),
+        _ => None,
+    }
+}
+
+/// 输入端 seek 参数（放在 `-i` 之前）
+pub fn input_seek_args(start: f64, duration: Option<f64>) -> Vec<String> {
+    let mut args = vec!["-ss".to_string(), format!("{start:.3}")];
+    if let Some(duration) = duration {
+        args.push("-t".to_string());
+        args.push(format!("{duration:.3}"));
+    }
+    args
+}
+
+/// 多段剪辑时只处理选中时间段内音频帧的 `aselect` 表达式，用于响度测量
+pub fn select_expr(ranges: &[TimeRange]) -> String {
+    ranges
+        .iter()
+        .map(|r| match r.end {
+            Some(end) => format!("between(t,{:.3},{end:.3})", r.start),
+            None => format!("gte(t,{:.3})", r.start),
+        })
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+/// 多段剪辑滤镜图及其输出连接标签
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrimGraph {
+    pub graph: FilterGraph,
+    pub video: String,
+    pub audio: Vec<String>,
+}
+
+/// 构建多段剪辑的滤镜图
+///
+/// - `video_filters`：按原时间轴作用于整段视频的滤镜（字幕等），在截取之前执行
+/// - `video_post`：拼接之后的视频滤镜（如 VAAPI 上传）
+/// - `audio_inputs`：参与拼接的音频流，如 `0:1`
+/// - `audio_post`：拼接之后每条音轨的滤镜（如响度标准化），与 `audio_inputs` 一一对应
+pub fn concat_graph(
+    ranges: &[TimeRange],
+    video_filters: FilterChain,
+    video_post: FilterChain,
+    audio_inputs: &[String],
+    audio_post: &[FilterChain],
+) -> TrimGraph {
+    let n = ranges.len();
+    let mut graph = FilterGraph::new();
+
+    let mut split = video_filters.input("0:V:0").filter(Filter::new("split").arg(n as u32));
+    for i in 0..n {
+        split = split.output(&format!("v{i}"));
+    }
+    graph.push(split);
+
+    for (j, input) in audio_inputs.iter().enumerate() {
+        let mut asplit = FilterChain::new()
+            .input(input)
+            .filter(Filter::new("asplit").arg(n as u32));
+        for i in 0..n {
+            asplit = asplit.output(&format!("a{j}_{i}"));
+        }
+        graph.push(asplit);
+    }
+
+    let mut concat = FilterChain::new();
+    for (i, range) in ranges.iter().enumerate() {
+        graph.push(
+            FilterChain::new()
+                .input(&format!("v{i}"))
+                .filter(trim_filter("trim", range))
+                .filter(Filter::new("setpts").arg("PTS-STARTPTS"))
+                .output(&format!("vt{i}")),
+        );
+        concat = concat.input(&format!("vt{i}"));
+        for j in 0..audio_inputs.len() {
+            graph.push(
+                FilterChain::new()
+                    .input(&format!("a{j}_{i}"))
+                    .filter(trim_filter("atrim", range))
+                    .filter(Filter::new("asetpts").arg("PTS-STARTPTS"))
+                    .output(&format!("at{j}_{i}")),
+            );
+            concat = concat.input(&format!("at{j}_{i}"));
+        }
+    }
+
+    let concat = concat.filter(
+        Filter::new("concat")
+            .opt("n", n as u32)
+            .opt("v", 1)
+            .opt("a", audio_inputs.len() as u32),
+    );
+    let mut concat = concat.output("vcat");
+    for j in 0..audio_inputs.len() {
+        concat = concat.output(&format!("acat{j}"));
+    }
+    graph.push(concat);
+
+    let video = if video_post.is_empty() {
+        "vcat".to_string()
+    } else {
+        graph.push(video_post.input("vcat").output("vout"));
+        "vout".to_string()
+    };
+
+    let audio = (0..audio_inputs.len())
+        .map(|j| match audio_post.get(j) {
+            Some(post) if !post.is_empty() => {
+                let label = format!("aout{j}");
+                graph.push(post.clone().input(&format!("acat{j}")).output(&label));
+                label
+            }
+            _ => format!("acat{j}"),
+        })
+        .collect();
+
+    TrimGraph { graph, video, audio }
+}
+
+fn trim_filter(name: &str, range: &TimeRange) -> Filter {
+    let filter = Filter::new(name).opt("start", range.start);
+    match range.end {
+        Some(end) => filter.opt("end", end),
+        None => filter,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: f64, end: Option<f64>) -> TimeRange {
+        TimeRange { start, end }
+    }
+
+    #[test]
+    fn test_validate_and_duration() {
+        let ranges = [range(90.0, Some(600.0)), range(700.0, None)];
+        assert!(validate_ranges(&ranges, 1440.0).is_ok());
+        assert_eq!(output_duration(&ranges, 1440.0), 510.0 + 740.0);
+        assert_eq!(output_duration(&ranges, 0.0), 0.0);
+        assert_eq!(output_duration(&[], 1440.0), 1440.0);
+
+        assert!(validate_ranges(&[range(10.0, Some(5.0))], 0.0).is_err());
+        assert!(validate_ranges(&[range(10.0, None), range(20.0, Some(30.0))], 0.0).is_err());
+        assert!(validate_ranges(&[range(10.0, Some(30.0)), range(20.0, Some(40.0))], 0.0).is_err());
+        assert!(validate_ranges(&[range(2000.0, None)], 1440.0).is_err());
+    }
+
+    #[test]
+    fn test_concat_graph() {
+        let ranges = [range(0.0, Some(10.0)), range(20.5, None)];
+        let subtitles = FilterChain::new().filter(Filter::new("subtitles").arg("a.ass"));
+        let trim = concat_graph(
+            &ranges,
+            subtitles,
+            FilterChain::new(),
+            &["0:1".to_string()],
+            &[],
+        );
+        assert_eq!(
+            trim.graph.to_string(),
+            "[0:V:0]subtitles=a.ass,split=2[v0][v1];[0:1]asplit=2[a0_0][a0_1];\
+             [v0]trim=start=0:end=10,setpts=PTS-STARTPTS[vt0];\
+             [a0_0]atrim=start=0:end=10,asetpts=PTS-STARTPTS[at0_0];\
+             [v1]trim=start=20.5,setpts=PTS-STARTPTS[vt1];\
+             [a0_1]atrim=start=20.5,asetpts=PTS-STARTPTS[at0_1];\
+             [vt0][at0_0][vt1][at0_1]concat=n=2:v=1:a=1[vcat][acat0]"
+        );
+        assert_eq!(trim.video, "vcat");
+        assert_eq!(trim.audio, ["acat0"]);
+
+        assert_eq!(
+            select_expr(&ranges),
+            "between(t,0.000,10.000)+gte(t,20.500)"
+        );
+    }
+}
@@ -0,0 +1,522 @@
+//! 画面处理：非方形像素校正、缩放与帧率转换、HDR 色调映射，以及输出像素格式与色彩信息
+//!
+//! 这些滤镜放在 `subtitles` 之前（旋转由 FFmpeg 自动旋转在滤镜之前完成）：
+//! 字幕在方向和宽高比正确的画面上按输出分辨率渲染，
+//! 不会随画面一起被旋转、拉伸，也不会因之后的缩放而模糊；HDR 源先映射到 SDR，
+//! 字幕颜色才能按 SDR 亮度显示。
+
+use crate::ffmpeg::encoder::EncoderFamily;
+use crate::ffmpeg::filter::Filter;
+use crate::types::{
+    EncodeParams, HdrMode, HdrTransfer, MediaInfo, PixelFormat, ScaleOptions, Scaler, SourceVideo,
+};
+
+/// 输出宽高的允许范围
+const DIMENSION_RANGE: std::ops::RangeInclusive<u32> = 16..=16384;
+
+/// 允许的最高输出帧率
+const MAX_FPS: f64 = 240.0;
+
+/// 检查缩放和帧率设置
+pub fn validate(params: &EncodeParams) -> Result<(), String> {
+    if let Some(scale) = &params.scale {
+        if scale.width.is_none() && scale.height.is_none() {
+            return Err("缩放需要指定宽度或高度".to_string());
+        }
+        for (name, value) in [("宽度", scale.width), ("高度", scale.height)] {
+            if let Some(value) = value
+                && !DIMENSION_RANGE.contains(&value)
+            {
+                return Err(format!("输出{name}超出范围: {value}"));
+            }
+        }
+    }
+    if let Some(fps) = params.fps
+        && !(fps.is_finite() && fps > 0.0 && fps <= MAX_FPS)
+    {
+        return Err(format!("输出帧率无效: {fps}"));
+    }
+    Ok(())
+}
+
+/// 从探测结果中取出主视频流（排除封面图）的画面信息
+pub fn source_video(info: &MediaInfo) -> Option<SourceVideo> {
+    let stream = info.video.iter().find(|v| !v.attached_pic)?;
+    Some(SourceVideo {
+        width: stream.width,
+        height: stream.height,
+        rotation: stream.rotation,
+        sar: stream
+            .sample_aspect_ratio
+            .as_deref()
+            .and_then(parse_ratio)
+            .filter(|(num, den)| num != den),
+        hdr: stream.hdr,
+        bit_depth: stream.bit_depth,
+        color_primaries: stream.color_primaries.clone(),
+        color_transfer: stream.color_transfer.clone(),
+        color_space: stream.color_space.clone(),
+        color_range: stream.color_range.clone(),
+    })
+}
+
+/// 解析 `32:27` 形式的比例，`0:1` 等无效值返回 `None`
+fn parse_ratio(s: &str) -> Option<(u32, u32)> {
+    let (num, den) = s.split_once(':')?;
+    let (num, den) = (num.parse().ok()?, den.parse().ok()?);
+    (num > 0 && den > 0).then_some((num, den))
+}
+
+/// 播放时需要顺时针旋转的角度，取最接近的 90 度倍数
+fn clockwise_rotation(src: &SourceVideo) -> i32 {
+    ((-src.rotation).rem_euclid(360) + 45) / 90 % 4 * 90
+}
+
+/// 校正像素宽高比后的宽度（取偶数）
+fn square_pixel_width(width: u32, sar: Option<(u32, u32)>) -> u32 {
+    match sar {
+        Some((num, den)) => {
+            let width = (f64::from(width) * f64::from(num) / f64::from(den)).round() as u32;
+            (width + 1) & !1
+        }
+        None => width,
+    }
+}
+
+/// 按播放方向和方形像素计算的画面尺寸，作为字幕的 `original_size`
+///
+/// FFmpeg 自动旋转在 `-vf` 之前执行，旋转 90/270 度后宽高互换，像素宽高比随之取倒数。
+pub fn display_size(src: &SourceVideo) -> (u32, u32) {
+    let (width, height, sar) = match clockwise_rotation(src) {
+        90 | 270 => (src.height, src.width, src.sar.map(|(num, den)| (den, num))),
+        _ => (src.width, src.height, src.sar),
+    };
+    (square_pixel_width(width, sar), height)
+}
+
+/// 把非方形像素拉伸为方形像素（旋转由 FFmpeg 自动完成）
+fn geometry_filters(src: &SourceVideo) -> Vec<Filter> {
+    if src.sar.is_none() {
+        return Vec::new();
+    }
+    vec![
+        Filter::new("scale")
+            .opt("w", display_size(src).0)
+            .opt("h", "ih"),
+        Filter::new("setsar").arg(1),
+    ]
+}
+
+/// 字幕滤镜之前的画面处理滤镜：先校正像素宽高比，再缩放、转换帧率
+pub fn pre_subtitle_filters(params: &EncodeParams) -> Vec<Filter> {
+    let mut filters = params
+        .source_video
+        .as_ref()
+        .map(geometry_filters)
+        .unwrap_or_default();
+    if let Some(scale) = &params.scale {
+        filters.push(scale_filter(scale));
+    }
+    if let Some(fps) = params.fps {
+        filters.push(Filter::new("fps").arg(fps_value(fps)));
+    }
+    if let Some(hdr) = source_hdr(params)
+        && tonemaps(params)
+    {
+        filters.extend(tonemap_filters(hdr));
+    }
+    filters
+}
+
+/// 源视频的 HDR 传输特性；SDR 源或尚未探测时为 `None`
+fn source_hdr(params: &EncodeParams) -> Option<HdrTransfer> {
+    params.source_video.as_ref()?.hdr
+}
+
+/// 是否以 10-bit HDR 输出
+pub fn preserves_hdr(params: &EncodeParams) -> bool {
+    source_hdr(params).is_some() && params.hdr_mode == HdrMode::Preserve
+}
+
+/// 是否把 HDR 源色调映射为 SDR
+fn tonemaps(params: &EncodeParams) -> bool {
+    source_hdr(params).is_some() && params.hdr_mode == HdrMode::Tonemap
+}
+
+/// H.264 和 VP8 虽然部分支持 10-bit，但播放设备普遍不支持其 HDR 输出
+fn is_sdr_codec(encoder: &str) -> bool {
+    encoder.starts_with("h264_") || matches!(encoder, "libx264" | "libvpx")
+}
+
+fn is_ten_bit(format: PixelFormat) -> bool {
+    matches!(
+        format,
+        PixelFormat::Yuv420p10 | PixelFormat::Yuv422p10 | PixelFormat::Yuv444p10
+    )
+}
+
+/// 编码器支持的输出像素格式
+fn supported_formats(encoder: &str) -> &'static [PixelFormat] {
+    use PixelFormat::*;
+    const ALL: &[PixelFormat] = &[Yuv420p, Yuv420p10, Yuv422p, Yuv422p10, Yuv444p, Yuv444p10];
+    const YUV420: &[PixelFormat] = &[Yuv420p, Yuv420p10];
+    const YUV420_8BIT: &[PixelFormat] = &[Yuv420p];
+
+    let next_gen = encoder.starts_with("hevc_") || encoder.starts_with("av1_");
+    match EncoderFamily::of(encoder) {
+        EncoderFamily::Nvenc if encoder.starts_with("hevc_") => {
+            &[Yuv420p, Yuv420p10, Yuv444p, Yuv444p10]
+        }
+        EncoderFamily::Nvenc if encoder.starts_with("h264_") => &[Yuv420p, Yuv444p],
+        EncoderFamily::Nvenc | EncoderFamily::Qsv | EncoderFamily::Vaapi | EncoderFamily::Amf => {
+            if next_gen {
+                YUV420
+            } else {
+                YUV420_8BIT
+            }
+        }
+        EncoderFamily::SvtAv1 => YUV420,
+        EncoderFamily::Aom | EncoderFamily::Rav1e => ALL,
+        EncoderFamily::Vpx if encoder == "libvpx-vp9" => ALL,
+        EncoderFamily::Software if matches!(encoder, "libx264" | "libx265") => ALL,
+        EncoderFamily::Vpx | EncoderFamily::Software => YUV420_8BIT,
+    }
+}
+
+/// 像素格式在该编码器上对应的 FFmpeg 名称；硬件编码器使用 nv12 / p010 等半平面格式
+pub fn ffmpeg_pix_fmt(format: PixelFormat, encoder: &str) -> &'static str {
+    let family = EncoderFamily::of(encoder);
+    match format {
+        PixelFormat::Yuv420p => match family {
+            EncoderFamily::Qsv | EncoderFamily::Vaapi | EncoderFamily::Amf => "nv12",
+            _ => "yuv420p",
+        },
+        PixelFormat::Yuv420p10 => match family {
+            EncoderFamily::Vaapi => "p010",
+            EncoderFamily::Nvenc | EncoderFamily::Qsv | EncoderFamily::Amf => "p010le",
+            _ => "yuv420p10le",
+        },
+        PixelFormat::Yuv422p => "yuv422p",
+        PixelFormat::Yuv422p10 => "yuv422p10le",
+        PixelFormat::Yuv444p => "yuv444p",
+        PixelFormat::Yuv444p10 if family == EncoderFamily::Nvenc => "yuv444p16le",
+        PixelFormat::Yuv444p10 => "yuv444p10le",
+    }
+}
+
+/// 确定输出像素格式
+///
+/// 未指定时使用 4:2:0：保留 HDR 或源为 10-bit 且编码器为 HEVC/AV1/VP9 时输出 10-bit，否则 8-bit
+/// （10-bit H.264 的硬件解码支持很差）。
+pub fn output_format(params: &EncodeParams, encoder: &str) -> Result<PixelFormat, String> {
+    let supported = supported_formats(encoder);
+    let format = match params.pix_fmt {
+        Some(format) => format,
+        None => {
+            let source_ten_bit = params
+                .source_video
+                .as_ref()
+                .and_then(|src| src.bit_depth)
+                .is_some_and(|depth| depth >= 10);
+            let ten_bit = preserves_hdr(params)
+                || (source_ten_bit && !tonemaps(params) && !is_sdr_codec(encoder));
+            if ten_bit && supported.contains(&PixelFormat::Yuv420p10) {
+                PixelFormat::Yuv420p10
+            } else {
+                PixelFormat::Yuv420p
+            }
+        }
+    };
+
+    if !supported.contains(&format) {
+        return Err(format!(
+            "{encoder} 不支持 {} 像素格式",
+            ffmpeg_pix_fmt(format, encoder)
+        ));
+    }
+    if preserves_hdr(params) && !is_ten_bit(format) {
+        return Err("保留 HDR 需要 10-bit 输出像素格式".to_string());
+    }
+    Ok(format)
+}
+
+/// 检查选定的编码器能否输出要求的像素格式，以及能否按要求处理 HDR 源
+pub fn validate_output(params: &EncodeParams, encoder: &str) -> Result<(), String> {
+    if preserves_hdr(params)
+        && (is_sdr_codec(encoder) || !supported_formats(encoder).contains(&PixelFormat::Yuv420p10))
+    {
+        return Err(format!(
+            "{encoder} 无法输出 10-bit HDR，请改用 HEVC、AV1 或 VP9 编码，或选择色调映射为 SDR"
+        ));
+    }
+    output_format(params, encoder).map(|_| ())
+}
+
+/// 以 zscale 转到线性光，在 BT.709 色域内用 Hable 曲线映射到 SDR，再转回 8-bit BT.709
+fn tonemap_filters(hdr: HdrTransfer) -> Vec<Filter> {
+    vec![
+        Filter::new("zscale")
+            .opt("tin", transfer_name(hdr))
+            .opt("pin", "bt2020")
+            .opt("min", "bt2020nc")
+            .opt("t", "linear")
+            .opt("npl", 100),
+        Filter::new("format").arg("gbrpf32le"),
+        Filter::new("zscale").opt("p", "bt709"),
+        Filter::new("tonemap").arg("hable").opt("desat", 0),
+        Filter::new("zscale")
+            .opt("t", "bt709")
+            .opt("m", "bt709")
+            .opt("r", "tv"),
+        Filter::new("format").arg("yuv420p"),
+    ]
+}
+
+/// VAAPI 上传到显存前的像素格式
+pub fn upload_format(params: &EncodeParams, encoder: &str) -> &'static str {
+    let format = output_format(params, encoder).unwrap_or(PixelFormat::Yuv420p);
+    ffmpeg_pix_fmt(format, encoder)
+}
+
+/// 输出的像素格式和色彩信息参数
+///
+/// 色调映射后标记为 BT.709，保留 HDR 时标记为 BT.2020，其余情况沿用源视频的色彩信息。
+pub fn color_args(params: &EncodeParams, encoder: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    // VAAPI 的像素格式由上传前的 format 滤镜决定
+    if EncoderFamily::of(encoder) != EncoderFamily::Vaapi {
+        let format = output_format(params, encoder).unwrap_or(PixelFormat::Yuv420p);
+        args.push("-pix_fmt".to_string());
+        args.push(ffmpeg_pix_fmt(format, encoder).to_string());
+    }
+
+    let Some(src) = &params.source_video else {
+        return args;
+    };
+    let range = src.color_range.as_deref();
+    let (primaries, transfer, matrix, range) = match (src.hdr, params.hdr_mode) {
+        (Some(_), HdrMode::Tonemap) => (Some("bt709"), Some("bt709"), Some("bt709"), Some("tv")),
+        (Some(hdr), HdrMode::Preserve) => (
+            Some("bt2020"),
+            Some(transfer_name(hdr)),
+            Some("bt2020nc"),
+            range,
+        ),
+        (None, _) => (
+            src.color_primaries.as_deref(),
+            src.color_transfer.as_deref(),
+            src.color_space.as_deref(),
+            range,
+        ),
+    };
+    for (key, value) in [
+        ("-color_primaries", primaries),
+        ("-color_trc", transfer),
+        ("-colorspace", matrix),
+        ("-color_range", range),
+    ] {
+        if let Some(value) = value.filter(|v| !matches!(*v, "unknown" | "unspecified" | "reserved"))
+        {
+            args.push(key.to_string());
+            args.push(value.to_string());
+        }
+    }
+    args
+}
+
+fn transfer_name(hdr: HdrTransfer) -> &'static str {
+    match hdr {
+        HdrTransfer::Pq => "smpte2084",
+        HdrTransfer::Hlg => "arib-std-b67",
+    }
+}
+
+/// 输出画面高度，与 [`scale_filter`] 的计算方式一致
+pub fn output_height(params: &EncodeParams) -> Option<u32> {
+    let (width, height) = display_size(params.source_video.as_ref()?);
+    let Some(scale) = &params.scale else {
+        return Some(height);
+    };
+    let even = |v: f64| (v / 2.0).round() as u32 * 2;
+    let (w, h) = (f64::from(width), f64::from(height));
+    Some(match (scale.width, scale.height) {
+        (Some(tw), Some(th)) if scale.keep_aspect => {
+            even(h * (f64::from(tw & !1) / w).min(f64::from(th & !1) / h))
+        }
+        (_, Some(th)) => th & !1,
+        (Some(tw), None) => even(h * f64::from(tw & !1) / w),
+        (None, None) => height & !1,
+    })
+}
+
+/// 缩放滤镜，尺寸取偶数以满足 yuv420p 等色度抽样格式的要求
+///
+/// 只指定一边时另一边用 `-2` 按宽高比计算并取偶数。
+fn scale_filter(scale: &ScaleOptions) -> Filter {
+    let even = |v: u32| i64::from(v & !1);
+    let filter = Filter::new("scale");
+    let filter = match (scale.width, scale.height) {
+        (Some(w), Some(h)) if scale.keep_aspect => filter
+            .opt("w", even(w))
+            .opt("h", even(h))
+            .opt("force_original_aspect_ratio", "decrease")
+            .opt("force_divisible_by", 2),
+        (Some(w), Some(h)) => filter.opt("w", even(w)).opt("h", even(h)),
+        (Some(w), None) => filter.opt("w", even(w)).opt("h", -2),
+        (None, Some(h)) => filter.opt("w", -2).opt("h", even(h)),
+        (None, None) => filter.opt("w", "trunc(iw/2)*2").opt("h", "trunc(ih/2)*2"),
+    };
+    filter.opt("flags", scaler_flag(scale.scaler))
+}
+
+fn scaler_flag(scaler: Scaler) -> &'static str {
+    match scaler {
+        Scaler::Bilinear => "bilinear",
+        Scaler::Bicubic => "bicubic",
+        Scaler::Lanczos => "lanczos",
+        Scaler::Spline => "spline",
+        Scaler::Area => "area",
+    }
+}
+
+/// 帧率参数，NTSC 帧率（23.976、29.97、59.94）写成精确的分数形式
+fn fps_value(fps: f64) -> String {
+    let ntsc = (fps * 1.001).round();
+    let exact = ntsc / 1.001;
+    if (exact - fps).abs() < 0.005 && (exact - ntsc).abs() > 0.01 {
+        format!("{ntsc}000/1001")
+    } else {
+        fps.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scale(width: Option<u32>, height: Option<u32>, keep_aspect: bool) -> ScaleOptions {
+        ScaleOptions {
+            width,
+            height,
+            keep_aspect,
+            scaler: Scaler::Bicubic,
+        }
+    }
+
+    #[test]
+    fn test_scale_filter() {
+        assert_eq!(
+            scale_filter(&scale(None, Some(720), true)).to_string(),
+            "scale=w=-2:h=720:flags=bicubic"
+        );
+        assert_eq!(
+            scale_filter(&scale(Some(1281), Some(721), true)).to_string(),
+            "scale=w=1280:h=720:force_original_aspect_ratio=decrease:force_divisible_by=2:flags=bicubic"
+        );
+        let mut stretch = scale(Some(640), Some(480), false);
+        stretch.scaler = Scaler::Lanczos;
+        assert_eq!(
+            scale_filter(&stretch).to_string(),
+            "scale=w=640:h=480:flags=lanczos"
+        );
+    }
+
+    #[test]
+    fn test_geometry_filters() {
+        // 竖拍手机视频：逆时针 -90 度，即播放时顺时针旋转 90 度
+        let phone = SourceVideo {
+            width: 1920,
+            height: 1080,
+            rotation: -90,
+            ..SourceVideo::default()
+        };
+        assert_eq!(display_size(&phone), (1080, 1920));
+        assert!(geometry_filters(&phone).is_empty());
+
+        // NTSC 宽屏 DVD
+        let dvd = SourceVideo {
+            width: 720,
+            height: 480,
+            sar: parse_ratio("32:27"),
+            ..SourceVideo::default()
+        };
+        assert_eq!(display_size(&dvd), (854, 480));
+        let filters: Vec<String> = geometry_filters(&dvd)
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        assert_eq!(filters, ["scale=w=854:h=ih", "setsar=1"]);
+
+        // 旋转后的变形画面：自动旋转后宽 480、像素宽高比 27:32
+        let rotated_dvd = SourceVideo {
+            rotation: -90,
+            ..dvd
+        };
+        assert_eq!(display_size(&rotated_dvd), (406, 720));
+        assert_eq!(parse_ratio("0:1"), None);
+    }
+
+    #[test]
+    fn test_tonemap_filters() {
+        let filters: Vec<String> = tonemap_filters(HdrTransfer::Hlg)
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        assert_eq!(
+            filters.join(","),
+            "zscale=tin=arib-std-b67:pin=bt2020:min=bt2020nc:t=linear:npl=100,format=gbrpf32le,\
+             zscale=p=bt709,tonemap=hable:desat=0,zscale=t=bt709:m=bt709:r=tv,format=yuv420p"
+        );
+    }
+
+    #[test]
+    fn test_output_format() {
+        let mut params: EncodeParams = serde_json::from_value(serde_json::json!({
+            "videoPath": "/videos/in.mkv",
+            "subtitlePath": "/subs/a.ass",
+            "outputDir": "/videos/out",
+            "outputFormat": "mkv",
+            "videoCodec": "libx265",
+            "crf": 23,
+            "subtitleEncoding": "utf8",
+            "subtitleStyle": "default",
+        }))
+        .unwrap();
+        params.source_video = Some(SourceVideo {
+            bit_depth: Some(10),
+            color_primaries: Some("bt709".to_string()),
+            color_range: Some("tv".to_string()),
+            ..SourceVideo::default()
+        });
+
+        assert_eq!(
+            output_format(&params, "libx265"),
+            Ok(PixelFormat::Yuv420p10)
+        );
+        assert_eq!(output_format(&params, "libx264"), Ok(PixelFormat::Yuv420p));
+        assert_eq!(output_format(&params, "h264_qsv"), Ok(PixelFormat::Yuv420p));
+        assert_eq!(
+            color_args(&params, "hevc_nvenc").join(" "),
+            "-pix_fmt p010le -color_primaries bt709 -color_range tv"
+        );
+
+        params.pix_fmt = Some(PixelFormat::Yuv444p10);
+        assert!(output_format(&params, "libx264").is_ok());
+        assert!(output_format(&params, "libsvtav1").is_err());
+
+        params.pix_fmt = None;
+        params.hdr_mode = HdrMode::Preserve;
+        params.source_video.as_mut().unwrap().hdr = Some(HdrTransfer::Pq);
+        assert!(validate_output(&params, "libx264").is_err());
+        assert!(validate_output(&params, "hevc_vaapi").is_ok());
+        assert_eq!(upload_format(&params, "hevc_vaapi"), "p010");
+    }
+
+    #[test]
+    fn test_fps_value() {
+        assert_eq!(fps_value(23.976), "24000/1001");
+        assert_eq!(fps_value(29.97), "30000/1001");
+        assert_eq!(fps_value(24.0), "24");
+        assert_eq!(fps_value(12.5), "12.5");
+    }
+}
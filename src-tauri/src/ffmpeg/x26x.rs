@@ -0,0 +1,281 @@
+//! libx264 / libx265 高级选项：preset、tune、profile/level、关键帧间隔、B 帧和参数透传
+//!
+//! libx265 不读取 FFmpeg 通用的 `-pass` 和 `-level`，两遍编码参数和 level 与用户透传的参数
+//! 合并为同一个 `-x265-params`（重复指定时 FFmpeg 只使用最后一个）。
+
+use crate::ffmpeg::args::PassSpec;
+use crate::ffmpeg::video;
+use crate::types::{EncodeParams, PixelFormat};
+
+const PRESETS: &[&str] = &[
+    "ultrafast",
+    "superfast",
+    "veryfast",
+    "faster",
+    "fast",
+    "medium",
+    "slow",
+    "slower",
+    "veryslow",
+    "placebo",
+];
+
+const X264_TUNES: &[&str] = &[
+    "film",
+    "animation",
+    "grain",
+    "stillimage",
+    "fastdecode",
+    "zerolatency",
+    "psnr",
+    "ssim",
+];
+
+const X265_TUNES: &[&str] = &["animation", "grain", "fastdecode", "zerolatency", "psnr", "ssim"];
+
+const LEVELS: &[&str] = &[
+    "1", "1b", "1.1", "1.2", "1.3", "2", "2.1", "2.2", "3", "3.1", "3.2", "4", "4.1", "4.2", "5",
+    "5.1", "5.2", "6", "6.1", "6.2", "8.5",
+];
+
+/// 由构建器控制或会写入额外文件的参数，不允许透传
+const RESERVED_PARAMS: &[&str] = &[
+    "pass",
+    "stats",
+    "crf",
+    "qp",
+    "bitrate",
+    "vbv-maxrate",
+    "vbv-bufsize",
+    "preset",
+    "tune",
+    "profile",
+    "level",
+    "level-idc",
+    "keyint",
+    "bframes",
+    "input-depth",
+    "output-depth",
+    "input-csp",
+    "csv",
+    "csv-log-level",
+    "recon",
+    "dump-yuv",
+    "analysis-save",
+    "analysis-load",
+];
+
+/// 允许的最大关键帧间隔（帧）
+const MAX_KEYINT: u32 = 10000;
+
+/// 允许的最大连续 B 帧数
+const MAX_BFRAMES: u32 = 16;
+
+fn is_x26x(encoder: &str) -> bool {
+    matches!(encoder, "libx264" | "libx265")
+}
+
+/// profile 允许的输出像素格式；未知 profile 返回 `None`
+fn profile_formats(encoder: &str, profile: &str) -> Option<&'static [PixelFormat]> {
+    use PixelFormat::*;
+    let formats: &[PixelFormat] = match (encoder, profile) {
+        ("libx264", "baseline" | "main" | "high") | ("libx265", "main" | "mainstillpicture") => {
+            &[Yuv420p]
+        }
+        ("libx264", "high10") | ("libx265", "main10") => &[Yuv420p, Yuv420p10],
+        ("libx264", "high422") | ("libx265", "main422-10") => {
+            &[Yuv420p, Yuv420p10, Yuv422p, Yuv422p10]
+        }
+        ("libx265", "main444-8") => &[Yuv420p, Yuv422p, Yuv444p],
+        ("libx264", "high444") | ("libx265", "main444-10") => {
+            &[Yuv420p, Yuv420p10, Yuv422p, Yuv422p10, Yuv444p, Yuv444p10]
+        }
+        _ => return None,
+    };
+    Some(formats)
+}
+
+/// 检查高级选项；`format` 为实际输出的像素格式，用于检查 profile
+///
+/// 选定的编码器不是 libx264 / libx265 时（如自动选择了硬件编码器）忽略这些选项。
+pub fn validate(params: &EncodeParams, encoder: &str, format: PixelFormat) -> Result<(), String> {
+    let options = &params.x26x;
+    if !is_x26x(encoder) {
+        if *options != Default::default() {
+            log::warn!("{encoder} 不支持 x264/x265 高级选项，已忽略");
+        }
+        return Ok(());
+    }
+
+    if let Some(preset) = &options.preset
+        && !PRESETS.contains(&preset.as_str())
+    {
+        return Err(format!("未知的 preset: {preset}"));
+    }
+    let tunes = if encoder == "libx265" { X265_TUNES } else { X264_TUNES };
+    if let Some(tune) = &options.tune
+        && !tunes.contains(&tune.as_str())
+    {
+        return Err(format!("{encoder} 不支持 tune={tune}"));
+    }
+    if let Some(profile) = &options.profile {
+        let formats = profile_formats(encoder, profile)
+            .ok_or_else(|| format!("{encoder} 不支持 profile={profile}"))?;
+        if !formats.contains(&format) {
+            return Err(format!(
+                "profile={profile} 不支持 {} 像素格式",
+                video::ffmpeg_pix_fmt(format, encoder)
+            ));
+        }
+    }
+    if let Some(level) = &options.level
+        && !LEVELS.contains(&level.as_str())
+    {
+        return Err(format!("未知的 level: {level}"));
+    }
+    if let Some(keyint) = options.keyint
+        && !(1..=MAX_KEYINT).contains(&keyint)
+    {
+        return Err(format!("关键帧间隔超出范围: {keyint}"));
+    }
+    if let Some(bframes) = options.bframes
+        && bframes > MAX_BFRAMES
+    {
+        return Err(format!("B 帧数超出范围: {bframes}"));
+    }
+
+    for (key, value) in &options.params {
+        if key.is_empty()
+            || !key
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            return Err(format!("非法的编码器参数名: {key}"));
+        }
+        if RESERVED_PARAMS.contains(&key.as_str()) {
+            return Err(format!("编码器参数 {key} 由程序控制，不能透传"));
+        }
+        // `:` 是参数分隔符
+        if value.is_empty() || value.contains(':') || value.chars().any(char::is_whitespace) {
+            return Err(format!("编码器参数 {key} 的值无效: {value:?}"));
+        }
+    }
+    Ok(())
+}
+
+/// 覆盖速度档位的 preset；编码器不是 libx264 / libx265 时返回 `None`
+pub fn preset<'a>(params: &'a EncodeParams, encoder: &str) -> Option<&'a str> {
+    params.x26x.preset.as_deref().filter(|_| is_x26x(encoder))
+}
+
+/// 除 preset 外的高级选项参数，包括 libx265 两遍编码的 `pass`/`stats`
+pub fn args(params: &EncodeParams, encoder: &str, pass: Option<PassSpec>) -> Vec<String> {
+    if !is_x26x(encoder) {
+        return Vec::new();
+    }
+    let options = &params.x26x;
+    let x265 = encoder == "libx265";
+    let mut args = Vec::new();
+    let mut codec_params = Vec::new();
+
+    if let Some(tune) = &options.tune {
+        args.push("-tune".to_string());
+        args.push(tune.clone());
+    }
+    if let Some(profile) = &options.profile {
+        args.push("-profile:v".to_string());
+        args.push(profile.clone());
+    }
+    if let Some(level) = &options.level {
+        if x265 {
+            codec_params.push(format!("level-idc={level}"));
+        } else {
+            args.push("-level".to_string());
+            args.push(level.clone());
+        }
+    }
+    if let Some(keyint) = options.keyint {
+        args.push("-g".to_string());
+        args.push(keyint.to_string());
+    }
+    if let Some(bframes) = options.bframes {
+        args.push("-bf".to_string());
+        args.push(bframes.to_string());
+    }
+
+    if x265 && let Some(pass) = pass {
+        codec_params.push(format!("pass={}", pass.pass));
+        codec_params.push(format!("stats={}.log", pass.log_prefix));
+    }
+    codec_params.extend(options.params.iter().map(|(k, v)| format!("{k}={v}")));
+    if !codec_params.is_empty() {
+        args.push(if x265 { "-x265-params" } else { "-x264-params" }.to_string());
+        args.push(codec_params.join(":"));
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> EncodeParams {
+        serde_json::from_value(serde_json::json!({
+            "videoPath": "/videos/in.mkv",
+            "subtitlePath": "/subs/a.ass",
+            "outputDir": "/videos/out",
+            "outputFormat": "mkv",
+            "videoCodec": "libx265",
+            "crf": 23,
+            "subtitleEncoding": "utf8",
+            "subtitleStyle": "default",
+            "x26x": {
+                "tune": "animation",
+                "profile": "main10",
+                "level": "5.1",
+                "keyint": 240,
+                "bframes": 8,
+                "params": { "aq-mode": "3", "psy-rd": "1.5" },
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_x265_params_merged_with_pass() {
+        let params = params();
+        let pass = PassSpec {
+            pass: 2,
+            log_prefix: "ffsub-1",
+        };
+        assert_eq!(
+            args(&params, "libx265", Some(pass)).join(" "),
+            "-tune animation -profile:v main10 -g 240 -bf 8 \
+             -x265-params level-idc=5.1:pass=2:stats=ffsub-1.log:aq-mode=3:psy-rd=1.5"
+        );
+        assert!(args(&params, "hevc_nvenc", Some(pass)).is_empty());
+        assert!(validate(&params, "libx265", PixelFormat::Yuv420p10).is_ok());
+        assert!(validate(&params, "hevc_nvenc", PixelFormat::Yuv420p10).is_ok());
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut params = params();
+        assert_eq!(
+            validate(&params, "libx265", PixelFormat::Yuv444p).unwrap_err(),
+            "profile=main10 不支持 yuv444p 像素格式"
+        );
+        assert!(validate(&params, "libx264", PixelFormat::Yuv420p10).is_err());
+
+        params.x26x.profile = None;
+        params.x26x.tune = Some("film".to_string());
+        assert!(validate(&params, "libx264", PixelFormat::Yuv420p).is_ok());
+        assert!(validate(&params, "libx265", PixelFormat::Yuv420p).is_err());
+
+        params.x26x.params.insert("stats".to_string(), "/tmp/x.log".to_string());
+        assert!(validate(&params, "libx264", PixelFormat::Yuv420p).is_err());
+        params.x26x.params.clear();
+        params.x26x.params.insert("zones".to_string(), "0,100:q=20".to_string());
+        assert!(validate(&params, "libx264", PixelFormat::Yuv420p).is_err());
+    }
+}
@@ -3,7 +3,7 @@ mod ffmpeg;
 mod state;
 mod types;
 
-use commands::encode;
+use commands::{encode, preview};
 use state::AppState;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -20,15 +20,25 @@ pub fn run() {
                         .build(),
                 )?;
             }
+            // 硬件编码器探测需要多次测试编码，在后台进行，完成前自动选择使用软件编码器
+            ffmpeg::capability::probe_in_background();
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             encode::start_encode,
             encode::stop_encode,
+            encode::check_compatibility,
+            encode::preview_command,
+            encode::export_script,
             encode::get_ffmpeg_version,
             encode::get_video_info,
+            encode::get_media_info,
+            encode::list_encoders,
+            encode::list_vaapi_devices,
+            encode::probe_hw_encoders,
             encode::get_default_output_dir,
             encode::open_path,
+            preview::render_preview,
         ])
         .run(tauri::generate_context!())
         .expect("启动 Tauri 应用失败");
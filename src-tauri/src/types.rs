@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 /// FFmpeg 编码参数，前端通过 IPC 传入
@@ -12,16 +14,565 @@ pub struct EncodeParams {
     pub crf: i32,
     pub subtitle_encoding: String,
     pub subtitle_style: String,
+    /// 码率控制模式，缺省为 CRF（使用 `crf` 字段）
+    #[serde(default)]
+    pub rate_control: RateControl,
+    /// 速度档位，由各编码器映射为各自的预设选项
+    #[serde(default)]
+    pub speed: SpeedPreset,
+    /// VAAPI 使用的 DRI 渲染节点，如 `/dev/dri/renderD129`，缺省为 `renderD128`
+    #[serde(default)]
+    pub vaapi_device: Option<String>,
+    /// 编码器选择方式，缺省为自动（优先使用可用的硬件编码器）
+    #[serde(default)]
+    pub encoder_choice: EncoderChoice,
+    /// 自动选择的硬件编码器初始化失败时是否改用对应的软件编码器重新编码（指定编码器时不回退）
+    #[serde(default = "default_true")]
+    pub hw_fallback: bool,
+    /// 音频编码器，如 `aac`、`libopus`；缺省时复制源音频
+    #[serde(default)]
+    pub audio_codec: Option<String>,
+    /// 源音频无法直接写入目标容器时是否自动转码
+    #[serde(default = "default_true")]
+    pub auto_transcode_audio: bool,
+    /// 要保留的音轨及输出顺序，为空时由 FFmpeg 默认选择一条音轨
+    #[serde(default)]
+    pub audio_tracks: Vec<AudioTrack>,
+    /// EBU R128 响度标准化目标，缺省时不处理响度
+    #[serde(default)]
+    pub loudnorm: Option<LoudnormTarget>,
+    /// 要保留的时间段（按时间顺序），为空时保留全片
+    #[serde(default)]
+    pub trim: Vec<TimeRange>,
+    /// 输出分辨率，缺省时保持源分辨率
+    #[serde(default)]
+    pub scale: Option<ScaleOptions>,
+    /// 输出帧率，缺省时保持源帧率
+    #[serde(default)]
+    pub fps: Option<f64>,
+    /// 输出像素格式，缺省时按源位深和编码器自动选择 4:2:0 格式
+    #[serde(default)]
+    pub pix_fmt: Option<PixelFormat>,
+    /// libx264 / libx265 的高级编码选项，其他编码器忽略
+    #[serde(default)]
+    pub x26x: X26xOptions,
+    /// 用户自定义的额外 FFmpeg 参数
+    #[serde(default)]
+    pub extra_args: ExtraArgs,
+    /// HDR 源的处理方式，SDR 源忽略
+    #[serde(default)]
+    pub hdr_mode: HdrMode,
+    /// 输出文件名模板，相对于 `output_dir`，缺省为 `{stem}_sub.{ext}`
+    #[serde(default)]
+    pub output_template: Option<String>,
+    /// 兼容性检查确定需要丢弃源文件的内嵌字幕，不通过 IPC 传递
+    #[serde(skip)]
+    pub drop_subtitles: bool,
+    /// 源视频画面信息，由 runner 根据探测结果填入，不通过 IPC 传递
+    #[serde(skip)]
+    pub source_video: Option<SourceVideo>,
+    /// 各输出音轨的响度测量结果，由测量遍填入，不通过 IPC 传递
+    #[serde(skip)]
+    pub loudness: Vec<LoudnessStats>,
+}
+
+/// 输出分辨率设置
+///
+/// 只指定一边时另一边按源宽高比计算；两边都指定且保持宽高比时缩放到不超出该尺寸。
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScaleOptions {
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+    #[serde(default = "default_true")]
+    pub keep_aspect: bool,
+    #[serde(default)]
+    pub scaler: Scaler,
+}
+
+/// 缩放算法（`scale` 滤镜的 `flags`）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scaler {
+    Bilinear,
+    #[default]
+    Bicubic,
+    Lanczos,
+    Spline,
+    Area,
+}
+
+/// 源视频的画面信息，用于校正旋转和非方形像素、选择输出像素格式和保留色彩信息
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceVideo {
+    pub width: u32,
+    pub height: u32,
+    /// 逆时针旋转角度，与 [`VideoStream::rotation`] 相同
+    pub rotation: i32,
+    /// 采样宽高比（像素宽高比），方形像素或未知时为 `None`
+    pub sar: Option<(u32, u32)>,
+    pub hdr: Option<HdrTransfer>,
+    pub bit_depth: Option<u32>,
+    pub color_primaries: Option<String>,
+    pub color_transfer: Option<String>,
+    pub color_space: Option<String>,
+    pub color_range: Option<String>,
+}
+
+/// libx264 / libx265 的高级编码选项，未设置的项沿用编码器默认值
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct X26xOptions {
+    /// 覆盖速度档位对应的 preset，如 `slower`
+    #[serde(default)]
+    pub preset: Option<String>,
+    /// 如 `film`、`animation`、`grain`
+    #[serde(default)]
+    pub tune: Option<String>,
+    /// 如 `high`、`main10`
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// 如 `4.1`
+    #[serde(default)]
+    pub level: Option<String>,
+    /// 最大关键帧间隔（帧）
+    #[serde(default)]
+    pub keyint: Option<u32>,
+    /// 最大连续 B 帧数
+    #[serde(default)]
+    pub bframes: Option<u32>,
+    /// 透传给 `-x264-params` / `-x265-params` 的 `key=value` 选项
+    #[serde(default)]
+    pub params: BTreeMap<String, String>,
+}
+
+/// 额外的 FFmpeg 参数，按 shell 规则拆分
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ExtraArgs {
+    /// 输入选项，插入到 `-i` 之前
+    #[serde(default)]
+    pub input: String,
+    /// 输出选项，插入到输出路径之前
+    #[serde(default)]
+    pub output: String,
+}
+
+/// 输出像素格式（色度抽样与位深）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PixelFormat {
+    Yuv420p,
+    Yuv420p10,
+    Yuv422p,
+    Yuv422p10,
+    Yuv444p,
+    Yuv444p10,
+}
+
+/// 原视频时间轴上的一段时间（秒）
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct TimeRange {
+    pub start: f64,
+    /// 终点，缺省时持续到视频结尾（只能用于最后一段）
+    #[serde(default)]
+    pub end: Option<f64>,
+}
+
+/// 响度标准化目标
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoudnormTarget {
+    /// 目标综合响度（LUFS），EBU R128 为 -23
+    #[serde(default = "default_integrated")]
+    pub integrated: f64,
+    /// 真峰值上限（dBTP）
+    #[serde(default = "default_true_peak")]
+    pub true_peak: f64,
+    /// 目标响度范围（LU）
+    #[serde(default = "default_lra")]
+    pub lra: f64,
+}
+
+fn default_integrated() -> f64 {
+    -23.0
+}
+
+fn default_true_peak() -> f64 {
+    -1.0
+}
+
+fn default_lra() -> f64 {
+    7.0
+}
+
+/// `loudnorm` 测量遍得到的单条音轨响度统计
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoudnessStats {
+    /// 综合响度（LUFS），静音时为负无穷
+    pub input_i: f64,
+    /// 真峰值（dBTP）
+    pub input_tp: f64,
+    /// 响度范围（LU）
+    pub input_lra: f64,
+    pub input_thresh: f64,
+    pub target_offset: f64,
+    /// 源音轨采样率，标准化后恢复到该采样率
+    pub sample_rate: Option<u32>,
+}
+
+/// 输出中的一条音轨
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AudioTrack {
+    #[serde(flatten)]
+    pub source: AudioSource,
+    /// 是否标记为默认音轨
+    #[serde(default)]
+    pub default: bool,
+    /// 音轨标题元数据
+    #[serde(default)]
+    pub title: Option<String>,
+    /// 转码设置，缺省时使用 `audio_codec`（均未设置时复制）
+    #[serde(default)]
+    pub encode: Option<AudioEncode>,
+}
+
+/// 单条音轨的转码设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioEncode {
+    pub codec: AudioCodec,
+    /// 码率（kbps），缺省使用编码器默认值；FLAC 忽略
+    #[serde(default)]
+    pub bitrate: Option<u32>,
+    /// 输出声道数，如 5.1 下混为立体声时设为 2
+    #[serde(default)]
+    pub channels: Option<u32>,
+    /// 输出采样率（Hz）
+    #[serde(default)]
+    pub sample_rate: Option<u32>,
+}
+
+/// 可选的音频编码格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AudioCodec {
+    Aac,
+    Opus,
+    Mp3,
+    Flac,
+    Ac3,
+}
+
+/// 音轨来源
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "by", rename_all = "camelCase")]
+pub enum AudioSource {
+    /// 源文件中的流序号（即 [`AudioStream::index`]）
+    Index { index: u32 },
+    /// 语言代码，如 `jpn`，取第一条匹配的音轨
+    Language { language: String },
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// 编码器选择方式
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "mode", rename_all = "camelCase")]
+pub enum EncoderChoice {
+    /// 按 `video_codec` 自动替换为经测试可用的硬件编码器
+    #[default]
+    Auto,
+    /// 始终使用 `video_codec` 对应的软件编码器，保证结果可复现
+    Software,
+    /// 使用指定的具体编码器，如 `hevc_nvenc`，忽略 `video_codec`
+    Specific { encoder: String },
+}
+
+/// 与编码器无关的速度档位，越快压缩效率越低
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SpeedPreset {
+    Fastest,
+    Fast,
+    #[default]
+    Balanced,
+    Quality,
+    Best,
+}
+
+/// 码率控制模式，码率单位均为 kbps
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "mode", rename_all = "camelCase")]
+pub enum RateControl {
+    /// 恒定质量
+    #[default]
+    Crf,
+    /// 单遍平均码率
+    Abr { bitrate: u32 },
+    /// 恒定码率，`maxrate` 缺省等于目标码率，`bufsize` 缺省为两倍目标码率
+    #[serde(rename_all = "camelCase")]
+    Cbr {
+        bitrate: u32,
+        maxrate: Option<u32>,
+        bufsize: Option<u32>,
+    },
+    /// 两遍 VBR，先统计再按目标平均码率编码
+    TwoPass { bitrate: u32 },
+    /// 目标文件大小（十进制 MB），由时长和音频码率反推视频码率后两遍编码；
+    /// `audio_bitrate` 缺省时从源文件探测
+    #[serde(rename_all = "camelCase")]
+    TargetSize {
+        size_mb: f64,
+        audio_bitrate: Option<u32>,
+    },
 }
 
 /// 编码进度信息，通过事件推送到前端
 #[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct EncodeProgress {
     pub frame: u64,
     pub fps: f64,
     pub time: String,
     pub speed: String,
+    /// 当前这一遍的完成百分比
     pub percentage: f64,
+    /// 当前是第几遍（从 1 开始）
+    pub pass: u32,
+    /// 总遍数，单遍编码时为 1
+    pub total_passes: u32,
+}
+
+/// 编码任务启动结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncodeStarted {
+    pub output_path: String,
+    /// 实际使用的视频编码器
+    pub encoder: String,
+    /// 与所选设置的差异说明，如编码器不支持两遍编码
+    pub notes: Vec<String>,
+}
+
+/// 编码完成结果，随 `encode-complete` 事件推送
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncodeResult {
+    pub output_path: String,
+    /// 实际使用的视频编码器
+    pub encoder: String,
+    /// 输出文件实际大小（字节），文件不存在时为空
+    pub size_bytes: Option<u64>,
+    /// 目标大小模式下最终使用的视频码率（kbps）
+    pub video_bitrate: Option<u32>,
+    /// 目标大小模式下的编码次数（含超出后修正重试）
+    pub attempts: u32,
+    /// 硬件编码失败后改用软件编码时的记录
+    pub fallback: Option<EncoderFallback>,
+    /// 响度标准化前各音轨的测量结果，未启用时为空
+    pub loudness: Vec<LoudnessStats>,
+}
+
+/// 硬件编码器初始化失败后回退到软件编码器的记录
+#[derive(Debug, Clone, Serialize)]
+pub struct EncoderFallback {
+    pub from: String,
+    pub to: String,
+    pub reason: String,
+}
+
+/// 单个硬件编码器的测试编码结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncoderProbe {
+    pub encoder: String,
+    pub available: bool,
+    /// 不可用的原因（FFmpeg 错误输出的最后一行等）
+    pub reason: Option<String>,
+}
+
+/// 硬件编码器探测结果，按 FFmpeg 路径、版本和 VAAPI 设备缓存到磁盘
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncoderCapabilities {
+    pub ffmpeg_path: String,
+    pub ffmpeg_version: String,
+    /// 测试 VAAPI 编码器时使用的渲染节点（旧版缓存中缺省为空，会重新探测）
+    #[serde(default)]
+    pub vaapi_device: String,
+    /// 探测时间（Unix 秒）
+    pub probed_at: u64,
+    pub encoders: Vec<EncoderProbe>,
+}
+
+/// FFmpeg 中可用的一个视频编码器
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncoderInfo {
+    pub name: String,
+    pub description: String,
+    pub hardware: bool,
+    /// 硬件编码器是否通过测试编码；软件编码器始终为真
+    pub available: bool,
+}
+
+/// 按编码格式（如 `h264`、`hevc`、`av1`）分组的编码器列表
+#[derive(Debug, Clone, Serialize)]
+pub struct EncoderGroup {
+    pub codec: String,
+    pub encoders: Vec<EncoderInfo>,
+}
+
+/// 兼容性问题的严重程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IssueSeverity {
+    /// 已自动处理或不影响编码
+    Warning,
+    /// 必须修改参数才能编码
+    Error,
+}
+
+/// 编码参数与容器的一项兼容性问题
+#[derive(Debug, Clone, Serialize)]
+pub struct CompatIssue {
+    pub severity: IssueSeverity,
+    pub message: String,
+    /// 修改建议
+    pub suggestion: Option<String>,
+}
+
+/// 兼容性检查结果
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompatReport {
+    pub issues: Vec<CompatIssue>,
+    /// 源音频不兼容时自动选择的音频编码器
+    pub audio_codec: Option<String>,
+    /// 容器无法写入源文件的内嵌字幕，输出时丢弃（`-sn`）
+    pub drop_subtitles: bool,
+}
+
+/// 完整的媒体文件信息，包含所有流、章节和容器元数据
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaInfo {
+    /// ffprobe `format_name`，如 `matroska,webm`
+    pub format_name: String,
+    /// 总时长（秒）
+    pub duration: Option<f64>,
+    /// 文件大小（字节）
+    pub size: Option<u64>,
+    /// 总码率（bps）
+    pub bit_rate: Option<u64>,
+    /// 容器元数据标签
+    pub tags: BTreeMap<String, String>,
+    pub video: Vec<VideoStream>,
+    pub audio: Vec<AudioStream>,
+    pub subtitles: Vec<SubtitleStream>,
+    pub attachments: Vec<Attachment>,
+    pub chapters: Vec<Chapter>,
+}
+
+/// 视频流信息
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoStream {
+    /// 在文件中的流序号（`0:N`）
+    pub index: u32,
+    pub codec: String,
+    pub profile: Option<String>,
+    pub pix_fmt: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    /// 平均帧率
+    pub frame_rate: Option<f64>,
+    pub bit_depth: Option<u32>,
+    pub color_primaries: Option<String>,
+    pub color_transfer: Option<String>,
+    pub color_space: Option<String>,
+    pub color_range: Option<String>,
+    /// HDR 传输特性，SDR 视频为 `None`
+    pub hdr: Option<HdrTransfer>,
+    /// 显示矩阵的旋转角度（度，逆时针为正，范围 -180 到 180）
+    pub rotation: i32,
+    /// 像素宽高比，如 `1:1`
+    pub sample_aspect_ratio: Option<String>,
+    /// 显示宽高比，如 `16:9`
+    pub display_aspect_ratio: Option<String>,
+    /// 封面图等附加图片
+    pub attached_pic: bool,
+}
+
+/// HDR 传输特性
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HdrTransfer {
+    /// HDR10 / Dolby Vision 使用的 PQ（`smpte2084`）
+    Pq,
+    /// HLG（`arib-std-b67`）
+    Hlg,
+}
+
+/// HDR 源的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HdrMode {
+    /// 色调映射到 SDR（BT.709 8-bit），字幕按 SDR 亮度渲染
+    #[default]
+    Tonemap,
+    /// 保留 HDR：10-bit 输出并写入 BT.2020 色彩信息
+    Preserve,
+}
+
+/// 音频流信息
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioStream {
+    pub index: u32,
+    pub codec: String,
+    pub profile: Option<String>,
+    pub channels: u32,
+    pub channel_layout: Option<String>,
+    pub sample_rate: Option<u32>,
+    /// 码率（bps），MKV 取自 `BPS` 标签
+    pub bit_rate: Option<u64>,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub default: bool,
+}
+
+/// 字幕流信息
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubtitleStream {
+    pub index: u32,
+    pub codec: String,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub default: bool,
+    pub forced: bool,
+}
+
+/// 附件（通常为 MKV 内嵌字体）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub index: u32,
+    pub filename: Option<String>,
+    pub mimetype: Option<String>,
+}
+
+/// 章节
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Chapter {
+    /// 起止时间（秒）
+    pub start: f64,
+    pub end: f64,
+    pub title: Option<String>,
 }
 
 /// 视频文件信息
@@ -31,3 +582,50 @@ pub struct VideoInfo {
     pub duration: String,
     pub resolution: String,
 }
+
+/// 预览片段渲染结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewClip {
+    /// 临时文件路径
+    pub path: String,
+    /// 片段在原视频中的起始时间（秒）
+    pub start: f64,
+    /// 片段时长（秒）
+    pub duration: f64,
+}
+
+/// 一条可直接执行的 FFmpeg 命令
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandLine {
+    /// 完整参数列表，第一项为程序名
+    pub argv: Vec<String>,
+    /// POSIX shell（sh/bash/zsh）格式
+    pub posix: String,
+    /// PowerShell 格式
+    pub powershell: String,
+}
+
+/// 编码命令预览
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandPreview {
+    /// 实际使用的视频编码器
+    pub encoder: String,
+    pub output_path: String,
+    /// 依次执行的各遍命令
+    pub commands: Vec<CommandLine>,
+    /// 命令与程序内编码的差异说明
+    pub notes: Vec<String>,
+}
+
+/// 导出脚本的格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptShell {
+    /// `.sh` 脚本
+    Posix,
+    /// `.ps1` 脚本
+    PowerShell,
+}
@@ -52,11 +52,16 @@ const FORMAT_OPTIONS: DropdownOption<OutputFormat>[] = [
 	{ value: "mkv", label: "MKV (H.265)" },
 	{ value: "avi", label: "AVI" },
 	{ value: "mov", label: "MOV" },
+	{ value: "webm", label: "WebM (Opus 音频)" },
 ];
 
 const CODEC_OPTIONS: DropdownOption<VideoCodec>[] = [
 	{ value: "libx264", label: "H.264 (libx264)" },
 	{ value: "libx265", label: "H.265 (libx265)" },
+	{ value: "libsvtav1", label: "AV1 (SVT-AV1)" },
+	{ value: "libaom-av1", label: "AV1 (libaom)" },
+	{ value: "librav1e", label: "AV1 (rav1e)" },
+	{ value: "libvpx-vp9", label: "VP9 (libvpx)" },
 	{ value: "copy", label: "复制原始流" },
 ];
 
@@ -84,8 +84,8 @@ export function useEncode() {
 				dispatch({ type: "PROGRESS", payload: progress });
 			}
 		});
-		const unlistenComplete = onEncodeComplete((outputPath) => {
-			dispatch({ type: "COMPLETE", payload: outputPath });
+		const unlistenComplete = onEncodeComplete((result) => {
+			dispatch({ type: "COMPLETE", payload: result.outputPath });
 		});
 		const unlistenError = onEncodeError((error) => {
 			dispatch({ type: "ERROR", payload: error });
@@ -107,7 +107,10 @@ export function useEncode() {
 	const start = useCallback(async (params: EncodeParams) => {
 		dispatch({ type: "START" });
 		try {
-			await startEncodeApi(params);
+			const started = await startEncodeApi(params);
+			for (const note of started.notes) {
+				dispatch({ type: "LOG", payload: note });
+			}
 		} catch (err) {
 			dispatch({ type: "ERROR", payload: String(err) });
 		}
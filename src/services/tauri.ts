@@ -1,7 +1,20 @@
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import { open } from "@tauri-apps/plugin-dialog";
-import type { EncodeParams, EncodeProgress, VideoInfo } from "@/types/encode";
+import type {
+	CommandPreview,
+	CompatReport,
+	EncoderCapabilities,
+	EncoderGroup,
+	EncodeParams,
+	EncodeProgress,
+	EncodeResult,
+	EncodeStarted,
+	MediaInfo,
+	PreviewClip,
+	ScriptShell,
+	VideoInfo,
+} from "@/types/encode";
 
 export async function selectVideoFile(): Promise<string | null> {
 	return (
@@ -25,18 +38,63 @@ export async function selectOutputDir(): Promise<string | null> {
 	return (await open({ directory: true })) ?? null;
 }
 
-export const startEncode = (params: EncodeParams): Promise<string> =>
-	invoke<string>("start_encode", { params } as unknown as Record<string, unknown>);
+/** 开始编码，index 为文件名模板中 `{index}` 的值，缺省为 1 */
+export const startEncode = (params: EncodeParams, index?: number): Promise<EncodeStarted> =>
+	invoke<EncodeStarted>("start_encode", { params, index } as unknown as Record<string, unknown>);
+
+/** 检查编码参数与目标容器的兼容性，不启动编码 */
+export const checkCompatibility = (params: EncodeParams): Promise<CompatReport> =>
+	invoke<CompatReport>("check_compatibility", { params } as unknown as Record<string, unknown>);
+
+/** 生成编码命令而不执行 */
+export const previewCommand = (params: EncodeParams, index?: number): Promise<CommandPreview> =>
+	invoke<CommandPreview>("preview_command", { params, index } as unknown as Record<
+		string,
+		unknown
+	>);
+
+/** 把一组编码任务导出为批处理脚本，path 的扩展名须为 .sh / .ps1；文件已存在时需传 overwrite 才会覆盖 */
+export const exportScript = (
+	queue: EncodeParams[],
+	shell: ScriptShell,
+	path: string,
+	overwrite = false,
+): Promise<void> =>
+	invoke("export_script", { queue, shell, path, overwrite } as unknown as Record<string, unknown>);
 
 export const stopEncode = (): Promise<void> => invoke("stop_encode");
 
 export const getVideoInfo = (path: string): Promise<VideoInfo> =>
 	invoke<VideoInfo>("get_video_info", { path });
 
+/** 获取所有流、章节和容器元数据 */
+export const getMediaInfo = (path: string): Promise<MediaInfo> =>
+	invoke<MediaInfo>("get_media_info", { path });
+
+/** 获取硬件编码器测试编码结果，refresh 为 true 时忽略缓存重新探测，vaapiDevice 为测试 VAAPI 使用的渲染节点 */
+export const probeHwEncoders = (
+	refresh = false,
+	vaapiDevice?: string,
+): Promise<EncoderCapabilities> =>
+	invoke<EncoderCapabilities>("probe_hw_encoders", { refresh, vaapiDevice });
+
+/** 按编码格式分组列出所有视频编码器 */
+export const listEncoders = (vaapiDevice?: string): Promise<EncoderGroup[]> =>
+	invoke<EncoderGroup[]>("list_encoders", { vaapiDevice });
+
+export const listVaapiDevices = (): Promise<string[]> => invoke<string[]>("list_vaapi_devices");
+
 export const getFFmpegVersion = (): Promise<string> => invoke<string>("get_ffmpeg_version");
 
 export const getDefaultOutputDir = (): Promise<string> => invoke<string>("get_default_output_dir");
 
+/** 按时间点或字幕条目序号渲染一小段预览，二者都不传时从视频开头截取 duration 秒（默认 10 秒） */
+export const renderPreview = (
+	params: EncodeParams,
+	target: { timestamp?: number; cueIndex?: number; duration?: number } = {},
+): Promise<PreviewClip> =>
+	invoke<PreviewClip>("render_preview", { params, ...target } as unknown as Record<string, unknown>);
+
 export async function openPath(path: string): Promise<void> {
 	return invoke("open_path", { path } as unknown as Record<string, unknown>);
 }
@@ -57,7 +115,8 @@ function onEvent<T>(event: string, cb: (payload: T) => void): () => void {
 
 export const onEncodeProgress = (cb: (p: EncodeProgress) => void) => onEvent("encode-progress", cb);
 
-export const onEncodeComplete = (cb: (path: string) => void) => onEvent("encode-complete", cb);
+export const onEncodeComplete = (cb: (result: EncodeResult) => void) =>
+	onEvent("encode-complete", cb);
 
 export const onEncodeError = (cb: (err: string) => void) => onEvent("encode-error", cb);
 
@@ -1,11 +1,36 @@
-export type OutputFormat = "mp4" | "mkv" | "avi" | "mov";
+export type OutputFormat = "mp4" | "mkv" | "avi" | "mov" | "webm";
 
-export type VideoCodec = "libx264" | "libx265" | "copy";
+export type VideoCodec =
+	| "libx264"
+	| "libx265"
+	| "libsvtav1"
+	| "libaom-av1"
+	| "librav1e"
+	| "libvpx-vp9"
+	| "copy";
 
 export type SubtitleEncoding = "utf8" | "gbk" | "big5";
 
 export type SubtitleStyle = "default" | "custom";
 
+/** 码率控制模式，码率单位均为 kbps */
+export type RateControl =
+	| { mode: "crf" }
+	| { mode: "abr"; bitrate: number }
+	| { mode: "cbr"; bitrate: number; maxrate?: number; bufsize?: number }
+	| { mode: "twoPass"; bitrate: number }
+	/** 目标文件大小（十进制 MB），audioBitrate 缺省时从源文件探测 */
+	| { mode: "targetSize"; sizeMb: number; audioBitrate?: number };
+
+/** 与编码器无关的速度档位 */
+export type SpeedPreset = "fastest" | "fast" | "balanced" | "quality" | "best";
+
+/** 编码器选择方式：自动替换为硬件编码器、仅软件编码或指定具体编码器 */
+export type EncoderChoice =
+	| { mode: "auto" }
+	| { mode: "software" }
+	| { mode: "specific"; encoder: string };
+
 export interface EncodeParams {
 	videoPath: string;
 	subtitlePath: string;
@@ -15,6 +40,228 @@ export interface EncodeParams {
 	crf: number;
 	subtitleEncoding: SubtitleEncoding;
 	subtitleStyle: SubtitleStyle;
+	rateControl?: RateControl;
+	speed?: SpeedPreset;
+	/** VAAPI 使用的 DRI 渲染节点，缺省为 /dev/dri/renderD128 */
+	vaapiDevice?: string;
+	encoderChoice?: EncoderChoice;
+	/** 自动选择的硬件编码器初始化失败时改用软件编码器，缺省为 true；指定编码器时不回退 */
+	hwFallback?: boolean;
+	/** 音频编码器，如 aac、libopus；缺省时复制源音频 */
+	audioCodec?: string;
+	/** 源音频与容器不兼容时自动转码，缺省为 true */
+	autoTranscodeAudio?: boolean;
+	/** 要保留的音轨及输出顺序，缺省时由 FFmpeg 默认选择一条音轨 */
+	audioTracks?: AudioTrack[];
+	/** EBU R128 响度标准化目标，缺省时不处理 */
+	loudnorm?: LoudnormTarget;
+	/** 要保留的时间段（按时间顺序），缺省时保留全片 */
+	trim?: TimeRange[];
+	/** 输出分辨率，缺省时保持源分辨率 */
+	scale?: ScaleOptions;
+	/** 输出帧率，缺省时保持源帧率 */
+	fps?: number;
+	/** 输出像素格式，缺省时按源位深和编码器自动选择 4:2:0 格式 */
+	pixFmt?: PixelFormat;
+	/** libx264 / libx265 高级选项，其他编码器忽略 */
+	x26x?: X26xOptions;
+	/** 额外的 FFmpeg 参数，按 shell 规则拆分 */
+	extraArgs?: ExtraArgs;
+	/** HDR 源的处理方式，缺省为 tonemap */
+	hdrMode?: HdrMode;
+	/**
+	 * 输出文件名模板，相对于 outputDir，可用 `/` 分隔子目录，缺省为 `{stem}_sub.{ext}`。
+	 * 变量：`{stem}` `{ext}` `{codec}` `{crf}` `{height}` `{lang}` `{date}` `{index}`
+	 */
+	outputTemplate?: string;
+}
+
+/** 与程序生成的参数冲突或会读写任意文件的选项会被拒绝 */
+export interface ExtraArgs {
+	/** 输入选项，插入到 -i 之前 */
+	input?: string;
+	/** 输出选项，插入到输出路径之前 */
+	output?: string;
+}
+
+/** libx264 / libx265 高级选项，未设置的项沿用编码器默认值 */
+export interface X26xOptions {
+	/** 覆盖速度档位对应的 preset，如 slower */
+	preset?: string;
+	/** 如 film、animation、grain */
+	tune?: string;
+	/** 如 high、main10 */
+	profile?: string;
+	/** 如 4.1 */
+	level?: string;
+	/** 最大关键帧间隔（帧） */
+	keyint?: number;
+	/** 最大连续 B 帧数 */
+	bframes?: number;
+	/** 透传给 -x264-params / -x265-params 的选项 */
+	params?: Record<string, string>;
+}
+
+export type PixelFormat = "yuv420p" | "yuv420p10" | "yuv422p" | "yuv422p10" | "yuv444p" | "yuv444p10";
+
+/** pq：HDR10（smpte2084）；hlg：arib-std-b67 */
+export type HdrTransfer = "pq" | "hlg";
+
+/** tonemap：映射到 SDR BT.709；preserve：10-bit 输出并保留 BT.2020 色彩信息 */
+export type HdrMode = "tonemap" | "preserve";
+
+export type Scaler = "bilinear" | "bicubic" | "lanczos" | "spline" | "area";
+
+/** 只指定一边时另一边按源宽高比计算；两边都指定且保持宽高比时缩放到不超出该尺寸 */
+export interface ScaleOptions {
+	width?: number;
+	height?: number;
+	/** 缺省为 true */
+	keepAspect?: boolean;
+	/** 缺省为 bicubic */
+	scaler?: Scaler;
+}
+
+/** 原视频时间轴上的一段时间（秒） */
+export interface TimeRange {
+	start: number;
+	/** 缺省时持续到视频结尾，只能用于最后一段 */
+	end?: number;
+}
+
+export interface LoudnormTarget {
+	/** 目标综合响度（LUFS），缺省 -23 */
+	integrated?: number;
+	/** 真峰值上限（dBTP），缺省 -1 */
+	truePeak?: number;
+	/** 目标响度范围（LU），缺省 7 */
+	lra?: number;
+}
+
+/** 音轨来源：源文件中的流序号（MediaInfo 中的 index）或语言代码 */
+export type AudioSource = { by: "index"; index: number } | { by: "language"; language: string };
+
+export type AudioTrack = AudioSource & {
+	default?: boolean;
+	title?: string;
+	/** 转码设置，缺省时复制（或使用 audioCodec） */
+	encode?: AudioEncode;
+};
+
+export type AudioCodec = "aac" | "opus" | "mp3" | "flac" | "ac3";
+
+export interface AudioEncode {
+	codec: AudioCodec;
+	/** 码率（kbps），缺省使用编码器默认值 */
+	bitrate?: number;
+	/** 输出声道数，如 5.1 下混为立体声时设为 2 */
+	channels?: number;
+	/** 输出采样率（Hz） */
+	sampleRate?: number;
+}
+
+export type IssueSeverity = "warning" | "error";
+
+export interface CompatIssue {
+	severity: IssueSeverity;
+	message: string;
+	suggestion: string | null;
+}
+
+export interface CompatReport {
+	issues: CompatIssue[];
+	/** 源音频不兼容时自动选择的音频编码器 */
+	audioCodec: string | null;
+	/** 容器无法写入源文件的内嵌字幕，输出时丢弃 */
+	dropSubtitles: boolean;
+}
+
+export interface EncodeStarted {
+	outputPath: string;
+	/** 实际使用的视频编码器 */
+	encoder: string;
+	/** 与所选设置的差异说明，如编码器不支持两遍编码 */
+	notes: string[];
+}
+
+/** 一条可直接执行的 FFmpeg 命令 */
+export interface CommandLine {
+	/** 完整参数列表，第一项为程序名 */
+	argv: string[];
+	posix: string;
+	powershell: string;
+}
+
+/** 编码命令预览 */
+export interface CommandPreview {
+	encoder: string;
+	outputPath: string;
+	/** 依次执行的各遍命令 */
+	commands: CommandLine[];
+	/** 命令与程序内编码的差异说明 */
+	notes: string[];
+}
+
+/** 导出脚本的格式 */
+export type ScriptShell = "posix" | "powershell";
+
+export interface EncodeResult {
+	outputPath: string;
+	encoder: string;
+	/** 输出文件实际大小（字节） */
+	sizeBytes: number | null;
+	/** 目标大小模式下最终使用的视频码率（kbps） */
+	videoBitrate: number | null;
+	attempts: number;
+	/** 硬件编码失败后改用软件编码时的记录 */
+	fallback: EncoderFallback | null;
+	/** 响度标准化前各音轨的测量结果，未启用时为空 */
+	loudness: LoudnessStats[];
+}
+
+export interface LoudnessStats {
+	/** 综合响度（LUFS），静音时为 null（JSON 无法表示负无穷） */
+	inputI: number | null;
+	inputTp: number | null;
+	inputLra: number | null;
+	inputThresh: number | null;
+	targetOffset: number | null;
+	sampleRate: number | null;
+}
+
+export interface EncoderFallback {
+	from: string;
+	to: string;
+	reason: string;
+}
+
+export interface EncoderProbe {
+	encoder: string;
+	available: boolean;
+	reason: string | null;
+}
+
+export interface EncoderCapabilities {
+	ffmpegPath: string;
+	ffmpegVersion: string;
+	/** 测试 VAAPI 编码器时使用的渲染节点 */
+	vaapiDevice: string;
+	/** 探测时间（Unix 秒） */
+	probedAt: number;
+	encoders: EncoderProbe[];
+}
+
+export interface EncoderInfo {
+	name: string;
+	description: string;
+	hardware: boolean;
+	/** 硬件编码器是否通过测试编码 */
+	available: boolean;
+}
+
+export interface EncoderGroup {
+	codec: string;
+	encoders: EncoderInfo[];
 }
 
 export interface VideoInfo {
@@ -23,6 +270,76 @@ export interface VideoInfo {
 	resolution: string;
 }
 
+export interface VideoStream {
+	index: number;
+	codec: string;
+	profile: string | null;
+	pixFmt: string | null;
+	width: number;
+	height: number;
+	frameRate: number | null;
+	bitDepth: number | null;
+	colorPrimaries: string | null;
+	colorTransfer: string | null;
+	colorSpace: string | null;
+	colorRange: string | null;
+	/** HDR 传输特性，SDR 视频为 null */
+	hdr: HdrTransfer | null;
+	/** 显示矩阵旋转角度（度，逆时针为正） */
+	rotation: number;
+	sampleAspectRatio: string | null;
+	displayAspectRatio: string | null;
+	attachedPic: boolean;
+}
+
+export interface AudioStream {
+	index: number;
+	codec: string;
+	profile: string | null;
+	channels: number;
+	channelLayout: string | null;
+	sampleRate: number | null;
+	bitRate: number | null;
+	language: string | null;
+	title: string | null;
+	default: boolean;
+}
+
+export interface SubtitleStream {
+	index: number;
+	codec: string;
+	language: string | null;
+	title: string | null;
+	default: boolean;
+	forced: boolean;
+}
+
+export interface Attachment {
+	index: number;
+	filename: string | null;
+	mimetype: string | null;
+}
+
+export interface Chapter {
+	start: number;
+	end: number;
+	title: string | null;
+}
+
+/** 完整媒体信息 */
+export interface MediaInfo {
+	formatName: string;
+	duration: number | null;
+	size: number | null;
+	bitRate: number | null;
+	tags: Record<string, string>;
+	video: VideoStream[];
+	audio: AudioStream[];
+	subtitles: SubtitleStream[];
+	attachments: Attachment[];
+	chapters: Chapter[];
+}
+
 export interface SubtitleInfo {
 	format: string;
 	encoding: string;
@@ -36,7 +353,10 @@ export interface EncodeProgress {
 	fps: number;
 	time: string;
 	speed: string;
+	/** 当前这一遍的完成百分比 */
 	percentage: number;
+	pass: number;
+	totalPasses: number;
 }
 
 export interface EncodeState {
@@ -46,3 +366,9 @@ export interface EncodeState {
 	outputPath: string | null;
 	error: string | null;
 }
+
+export interface PreviewClip {
+	path: string;
+	start: number;
+	duration: number;
+}